@@ -0,0 +1,71 @@
+// Scripts of every page, loaded in the head so high contrast applies before anything is shown
+
+// High contrast follows the system setting until it is toggled in the navigation
+const contrast = localStorage.getItem("contrast");
+if (contrast === "on" || (contrast === null && matchMedia("(prefers-contrast: more)").matches)) {
+  document.documentElement.classList.add("contrast");
+}
+
+// Theme chosen on this device, otherwise the default of the config
+const theme = localStorage.getItem("theme");
+if (theme) {
+  document.documentElement.dataset.theme = theme;
+}
+
+// Keeps the background, icon and sounds available offline, browsers only allow this
+// on localhost and https
+if ("serviceWorker" in navigator) {
+  navigator.serviceWorker.register("/sw.js").catch(() => {});
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  const toggle = document.getElementById("contrast");
+  toggle.setAttribute("aria-pressed", document.documentElement.classList.contains("contrast"));
+  toggle.addEventListener("click", () => {
+    const on = document.documentElement.classList.toggle("contrast");
+    localStorage.setItem("contrast", on ? "on" : "off");
+    toggle.setAttribute("aria-pressed", on);
+  });
+  const select = document.getElementById("theme");
+  select.value = document.documentElement.dataset.theme;
+  select.addEventListener("change", () => {
+    document.documentElement.dataset.theme = select.value;
+    localStorage.setItem("theme", select.value);
+  });
+});
+
+// Values the page hands to its script, rendered as JSON into `#page-data`
+function pageData() {
+  return JSON.parse(document.getElementById("page-data").textContent);
+}
+
+// Play sound `name` if sounds are enabled. With a `key` it is played only once,
+// even if the page is loaded again.
+function playSound(name, key) {
+  const audio = document.getElementById("sound-" + name);
+  if (!audio || (key && sessionStorage.getItem("sound-" + key))) {
+    return;
+  }
+  if (key) {
+    sessionStorage.setItem("sound-" + key, name);
+  }
+  // browsers refuse to play before the first click on the page, the cue is skipped then
+  audio.play().catch(() => {});
+}
+
+// Show the time left of `game`, as returned by the JSON API, in the progress bar
+function showProgress(game) {
+  const percent = game.timeout ? Math.round(100 * game.remaining / game.timeout) : 0;
+  const bar = document.getElementById("progress");
+  bar.style.width = percent + "%";
+  bar.className = game.time_level || "";
+  bar.parentElement.setAttribute("aria-valuenow", percent);
+}
+
+// Set the text of `element` only if it changed, so screen readers don't announce
+// a live region again on every update
+function setText(element, text) {
+  if (element.textContent !== text) {
+    element.textContent = text;
+  }
+}
@@ -0,0 +1,46 @@
+// Big screen: follow the game played on the controller page
+const texts = pageData().texts;
+
+function status(game) {
+  switch (game.phase) {
+    case "setup": return game.current_player ? texts.rolls.replace("{player}", game.current_player) : texts.waiting;
+    case "rolled": return texts.ready;
+    case "running": return game.current_player || "";
+    default: return texts.over;
+  }
+}
+
+// Play the sound cues for the change from the `previous` to the current state of the game
+function playSounds(previous, game) {
+  if (!previous || previous.phase !== "running") {
+    return;
+  }
+  if (game.phase === "running" && game.remaining <= 5 && game.remaining !== previous.remaining) {
+    playSound("tick");
+  }
+  if (game.phase === "finished" && game.remaining === 0) {
+    playSound("buzzer");
+  }
+  if (game.open_categories && game.open_categories.length < previous.open_categories.length) {
+    playSound("chime");
+  }
+}
+
+let previous = null;
+async function update() {
+  try {
+    const response = await fetch("/api/v1/game");
+    const game = await response.json();
+    playSounds(previous, game);
+    previous = game;
+    setText(document.getElementById("status"), status(game));
+    document.getElementById("letter").textContent = game.letter || "";
+    setText(document.querySelector("#category mark"), game.phase === "running" ? game.category : "");
+    document.getElementById("remaining").textContent = game.phase === "running" ? game.remaining + "s" : "";
+    document.getElementById("progress-bar").className = game.phase === "running" ? "running" : "";
+    showProgress(game);
+  } catch (e) {
+    // the server is restarting, try again with the next update
+  }
+}
+setInterval(update, 500);
@@ -0,0 +1,14 @@
+// Follow the round of the other player without reloading the page on a timer:
+// it is only loaded again once the round moved on
+const { phase, player } = pageData();
+
+setInterval(async () => {
+  try {
+    const game = await (await fetch("/api/v1/game")).json();
+    if (game.phase !== phase || game.current_player !== player) {
+      location.assign(game.phase === "running" ? "/timer" : "/round");
+    }
+  } catch (e) {
+    // the server is restarting, try again with the next update
+  }
+}, 1000);
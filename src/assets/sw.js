@@ -0,0 +1,63 @@
+// Keeps the embedded assets available when the connection to the game server drops out for a
+// moment, e.g. on a phone that switches networks. Pages and the API always come from the server.
+const CACHE = "slv-assets";
+const ASSETS = [
+  "/assets/app.css",
+  "/assets/app.js",
+  "/assets/display.css",
+  "/assets/display.js",
+  "/assets/round.js",
+  "/assets/timer.js",
+  "/assets/slv.png",
+  "/assets/icon.svg",
+  "/assets/manifest.webmanifest",
+  "/assets/sounds/tick.wav",
+  "/assets/sounds/buzzer.wav",
+  "/assets/sounds/chime.wav",
+];
+// The background of the room, which changes when another one is uploaded or the device joins
+// another room, so it is only answered from the cache while the server can't be reached
+const BACKGROUND = "/background";
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll([...ASSETS, BACKGROUND])).then(() => self.skipWaiting()));
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(self.clients.claim());
+});
+
+// Answer from the cache and refresh it in the background, so a new version of the app is
+// picked up with the next request
+self.addEventListener("fetch", (event) => {
+  const url = new URL(event.request.url);
+  if (event.request.method !== "GET" || url.origin !== location.origin) {
+    return;
+  }
+  if (url.pathname === BACKGROUND) {
+    event.respondWith(caches.open(CACHE).then((cache) => fetch(event.request)
+      .then((response) => {
+        if (response.ok) {
+          cache.put(event.request, response.clone());
+        }
+        return response;
+      })
+      .catch(() => cache.match(event.request))));
+    return;
+  }
+  if (!ASSETS.includes(url.pathname)) {
+    return;
+  }
+  event.respondWith(caches.open(CACHE).then(async (cache) => {
+    const cached = await cache.match(event.request);
+    const fetched = fetch(event.request)
+      .then((response) => {
+        if (response.ok) {
+          cache.put(event.request, response.clone());
+        }
+        return response;
+      })
+      .catch(() => cached);
+    return cached || fetched;
+  }));
+});
@@ -0,0 +1,53 @@
+// Timer page: sound cues, keyboard shortcuts and following the running round
+const data = pageData();
+
+if (data.running && data.remaining <= 5) {
+  playSound("tick", data.token + "-" + data.remaining);
+} else if (!data.running && data.remaining === 0) {
+  playSound("buzzer", data.token);
+}
+if (data.last_action === "Success" && data.remaining > 0) {
+  playSound("chime", data.token);
+}
+
+if (data.running && data.your_turn) {
+  // Success and Next with the keys of the terminal timer
+  document.addEventListener("keydown", (event) => {
+    if (event.ctrlKey || event.altKey || event.metaKey || event.target.closest("input, select, textarea")) {
+      return;
+    }
+    const form = { s: "success", n: "next" }[event.key.toLowerCase()];
+    if (form) {
+      event.preventDefault();
+      document.getElementById(form).requestSubmit();
+    }
+  });
+}
+
+// Follow the round without reloading the page on a timer. The page is only loaded again
+// when the round is over, or when it was played on in another tab of this player.
+let announced = data.remaining;
+async function update() {
+  try {
+    const game = await (await fetch("/api/v1/game")).json();
+    if (game.phase !== "running" || (data.your_turn && game.category !== data.category)) {
+      location.replace("/timer");
+      return;
+    }
+    document.getElementById("timeout").innerHTML = data.texts.timeout.replace("{timeout}", game.remaining);
+    showProgress(game);
+    setText(document.getElementById("category"), game.category);
+    if (game.remaining !== announced && (game.remaining === 10 || game.remaining === 5 || game.remaining % 30 === 0)) {
+      setText(document.getElementById("announcement"), data.texts.announce.replace("{remaining}", game.remaining));
+    }
+    if (game.remaining <= 5 && game.remaining !== announced) {
+      playSound("tick", data.token + "-" + game.remaining);
+    }
+    announced = game.remaining;
+  } catch (e) {
+    // the server is restarting, try again with the next update
+  }
+}
+if (data.running) {
+  setInterval(update, 500);
+}
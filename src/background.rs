@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Image types that can be used as background, with their file extensions
+const TYPES: &[(&str, &str)] = &[
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/webp", "webp"),
+    ("image/gif", "gif"),
+];
+
+/// Largest background image that can be uploaded
+pub const MAX_SIZE: usize = 10 * 1024 * 1024;
+
+/// Reasons why an image can't be used as background
+#[derive(Debug)]
+pub enum BackgroundError {
+    /// Only the image types of `TYPES` are supported
+    UnsupportedType,
+    /// The image is larger than `MAX_SIZE`
+    TooLarge,
+    /// The image couldn't be written to the data directory
+    Io(std::io::Error),
+}
+
+fn directory(data_dir: &Path) -> PathBuf {
+    data_dir.join("backgrounds")
+}
+
+/// Content type of the image at `path`, from its file extension
+pub fn content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let extension = if extension == "jpeg" { "jpg".to_string() } else { extension };
+    TYPES.iter().find(|(_, e)| *e == extension).map(|(content_type, _)| *content_type)
+}
+
+/// Background image of the game with the given `id`, if one was uploaded
+pub fn find(data_dir: &Path, id: &str) -> Option<PathBuf> {
+    TYPES.iter()
+        .map(|(_, extension)| directory(data_dir).join(format!("{id}.{extension}")))
+        .find(|path| path.is_file())
+}
+
+/// Store `data` of `content_type` as background image of the game `id`, replacing the
+/// previous one
+pub fn save(data_dir: &Path, id: &str, content_type: &str, data: &[u8]) -> Result<(), BackgroundError> {
+    let Some((_, extension)) = TYPES.iter().find(|(t, _)| *t == content_type) else {
+        return Err(BackgroundError::UnsupportedType);
+    };
+    if data.len() > MAX_SIZE {
+        return Err(BackgroundError::TooLarge);
+    }
+    delete(data_dir, id);
+    fs::create_dir_all(directory(data_dir))
+        .and_then(|_| fs::write(directory(data_dir).join(format!("{id}.{extension}")), data))
+        .map_err(BackgroundError::Io)
+}
+
+/// Remove the background image of the game `id`, so the default one is used again
+pub fn delete(data_dir: &Path, id: &str) {
+    while let Some(path) = find(data_dir, id) {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Couldn't delete background image {}: {}", path.display(), e);
+            return;
+        }
+    }
+}
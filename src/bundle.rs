@@ -0,0 +1,238 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::cards;
+use crate::i18n::Language;
+
+/// Marks a file as collection bundle, so other JSON files are rejected
+const FORMAT: &str = "slv-collection";
+
+/// Version of the bundle layout, bundles of later versions are rejected
+const VERSION: u32 = 1;
+
+/// A category collection with its translations in one file, to share it with another group
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Bundle {
+    format: String,
+    version: u32,
+    name: String,
+    /// Language the categories are written in
+    language: Language,
+    categories: Vec<String>,
+    /// Translations of the categories, by language
+    #[serde(default)]
+    translations: BTreeMap<Language, BTreeMap<String, String>>,
+    /// Time of the export
+    #[serde(default)]
+    exported: String,
+    /// Version of the app that exported the bundle
+    #[serde(default)]
+    app_version: String,
+}
+
+/// What to do when a collection with the name of the imported one exists
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflict {
+    /// Keep the existing collection and report the conflict
+    #[default]
+    Abort,
+    /// Replace the existing collection by the imported one
+    Replace,
+    /// Add the categories and translations the existing collection doesn't have yet
+    Merge,
+    /// Import the collection under a free name, like `name-2`
+    Rename,
+}
+
+impl FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(Conflict::Abort),
+            "replace" => Ok(Conflict::Replace),
+            "merge" => Ok(Conflict::Merge),
+            "rename" => Ok(Conflict::Rename),
+            _ => Err(format!("Unknown conflict handling '{s}', must be one of: abort, replace, merge, rename")),
+        }
+    }
+}
+
+/// Reasons why a bundle can't be imported
+#[derive(Debug)]
+pub enum BundleError {
+    /// The file isn't a collection bundle
+    Invalid(String),
+    /// The bundle was exported by a later version of the app
+    UnsupportedVersion(u32),
+    /// The name of the bundle can't be used for a collection
+    InvalidName(String),
+    /// A collection with the name exists and the conflict handling is `Conflict::Abort`
+    Exists(String),
+    /// Only collections written in the same language can be merged
+    LanguageMismatch { name: String, existing: Language, imported: Language },
+    /// The collection couldn't be written
+    Io(io::Error),
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BundleError::Invalid(reason) => write!(f, "Not a collection bundle: {reason}"),
+            BundleError::UnsupportedVersion(version) => write!(f, "Bundle version {version} is not supported, please update the app"),
+            BundleError::InvalidName(name) => write!(f, "'{name}' can't be used as collection name"),
+            BundleError::Exists(name) => write!(f, "There already is a collection named '{name}'"),
+            BundleError::LanguageMismatch { name, existing, imported } => write!(f, "Collection '{name}' is written in {existing}, the imported one in {imported}"),
+            BundleError::Io(e) => write!(f, "Couldn't write the collection: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+/// `name` with the suffix `-n`, shortened to remain a valid name
+fn renamed(name: &str, n: u32) -> String {
+    let suffix = format!("-{n}");
+    // names are ASCII, so bytes are characters
+    let base = &name[..name.len().min(cards::MAX_NAME_LENGTH - suffix.len())];
+    format!("{base}{suffix}")
+}
+
+/// The imported collection
+#[derive(Debug)]
+pub struct Imported {
+    /// Name the collection was imported as
+    pub name: String,
+    /// Number of categories that weren't in the collection before
+    pub added: usize,
+}
+
+impl Bundle {
+    /// Bundle of collection `name` with all of its translations, `None` if there is no such
+    /// collection
+    pub fn export(name: &str) -> Option<Bundle> {
+        if !cards::is_collection(name) {
+            return None;
+        }
+        let (language, categories) = cards::read_collection(name);
+        let translations = Language::ALL.into_iter()
+            .filter(|other| *other != language)
+            .map(|other| (other, cards::read_translations(name, other)))
+            .filter(|(_, translations)| !translations.is_empty())
+            .collect();
+        Some(Bundle
+            { format: FORMAT.to_string()
+            , version: VERSION
+            , name: name.to_string()
+            , language
+            , categories
+            , translations
+            , exported: Local::now().format("%Y-%m-%d %H:%M").to_string()
+            , app_version: env!("CARGO_PKG_VERSION").to_string()
+            })
+    }
+
+    /// Read a bundle from the content of its file
+    pub fn parse(content: &str) -> Result<Bundle, BundleError> {
+        let bundle: Bundle = serde_json::from_str(content).map_err(|e| BundleError::Invalid(e.to_string()))?;
+        if bundle.format != FORMAT {
+            return Err(BundleError::Invalid(format!("unknown format '{}'", bundle.format)));
+        }
+        if bundle.version > VERSION {
+            return Err(BundleError::UnsupportedVersion(bundle.version));
+        }
+        if bundle.categories.is_empty() {
+            return Err(BundleError::Invalid("no categories".to_string()));
+        }
+        if !cards::is_valid_name(&bundle.name) {
+            return Err(BundleError::InvalidName(bundle.name));
+        }
+        Ok(bundle)
+    }
+
+    /// The bundle as content of its file
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Name of the bundle file
+    pub fn file_name(&self) -> String {
+        format!("{}.slv.json", self.name)
+    }
+
+    /// Add the collection of the bundle, as `name` if given, handling an existing collection
+    /// of the same name as told by `conflict`
+    pub fn import(self, name: Option<&str>, conflict: Conflict) -> Result<Imported, BundleError> {
+        let name = name.unwrap_or(&self.name).to_string();
+        if !cards::is_valid_name(&name) {
+            return Err(BundleError::InvalidName(name));
+        }
+        // categories can't span lines, and empty ones would be dropped when the file is read
+        let categories: Vec<String> = self.categories.iter()
+            .map(|category| category.replace(['\r', '\n'], " ").trim().to_string())
+            .filter(|category| !category.is_empty() && !category.starts_with('#'))
+            .collect();
+
+        if !cards::is_collection(&name) {
+            return self.write(name, categories);
+        }
+        match conflict {
+            Conflict::Abort => Err(BundleError::Exists(name)),
+            Conflict::Replace => self.write(name, categories),
+            Conflict::Rename => {
+                let free = (2..).map(|n| renamed(&name, n)).find(|n| !cards::is_collection(n)).unwrap();
+                self.write(free, categories)
+            },
+            Conflict::Merge => {
+                let (language, mut existing) = cards::read_collection(&name);
+                if language != self.language {
+                    return Err(BundleError::LanguageMismatch { name, existing: language, imported: self.language });
+                }
+                let before = existing.len();
+                for category in categories {
+                    if !existing.contains(&category) {
+                        existing.push(category);
+                    }
+                }
+                let mut translations = BTreeMap::new();
+                for other in Language::ALL.into_iter().filter(|other| *other != language) {
+                    let mut merged = cards::read_translations(&name, other);
+                    for (category, translation) in self.translations.get(&other).into_iter().flatten() {
+                        merged.entry(category.clone()).or_insert_with(|| translation.clone());
+                    }
+                    translations.insert(other, merged);
+                }
+                cards::write_collection(&name, language, &existing, &translations).map_err(BundleError::Io)?;
+                Ok(Imported { added: existing.len() - before, name })
+            },
+        }
+    }
+
+    fn write(&self, name: String, categories: Vec<String>) -> Result<Imported, BundleError> {
+        let mut translations = self.translations.clone();
+        translations.remove(&self.language);
+        cards::write_collection(&name, self.language, &categories, &translations).map_err(BundleError::Io)?;
+        Ok(Imported { added: categories.len(), name })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renamed_names_stay_valid() {
+        assert_eq!(renamed("party", 2), "party-2");
+        let long = "a".repeat(cards::MAX_NAME_LENGTH);
+        for n in [2, 10, 1000] {
+            let name = renamed(&long, n);
+            assert!(cards::is_valid_name(&name), "{name}");
+            assert!(name.ends_with(&format!("-{n}")));
+        }
+    }
+}
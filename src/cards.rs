@@ -1,68 +1,197 @@
+use rand::Rng;
 use rand::seq::SliceRandom;
-use std::fs::{OpenOptions, read_to_string};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions, read_to_string};
 use std::io::{self, prelude::*};
+use std::path::Path;
 
-pub fn load_categories(name: &str) -> Vec<String> {
+use crate::i18n::Language;
+
+/// Directory of the category collections, one `<name>_edition.txt` file each
+const DIRECTORY: &str = "src/categories";
+
+/// Collections that come with the game, listed before the added ones
+const BUILTIN: [&str; 3] = ["default", "junior", "adult"];
+
+/// Longest name of a collection
+pub const MAX_NAME_LENGTH: usize = 40;
+
+fn path(name: &str) -> String {
+    format!("{DIRECTORY}/{name}_edition.txt")
+}
+
+fn translations_path(name: &str, language: Language) -> String {
+    format!("{DIRECTORY}/{name}_edition.{}.toml", language.code())
+}
+
+/// Names of all category collections: the built-in ones, then the added ones in alphabetical
+/// order
+pub fn collections() -> Vec<String> {
+    let mut added: Vec<String> = fs::read_dir(DIRECTORY).into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|file| Some(file.strip_suffix("_edition.txt")?.to_string()))
+        .filter(|name| is_valid_name(name) && !BUILTIN.contains(&name.as_str()))
+        .collect();
+    added.sort();
+    BUILTIN.iter().map(|name| name.to_string()).chain(added).collect()
+}
+
+/// Whether `name` can name a collection: lowercase letters, digits, `-` and `_`, so it is
+/// safe in file names and URLs
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Whether there is a collection named `name`
+pub fn is_collection(name: &str) -> bool {
+    is_valid_name(name) && Path::new(&path(name)).is_file()
+}
+
+/// Categories of collection `name` as written in its file, with the language declared by a
+/// `# language: xx` line (German if there is none). Other `#` lines are comments.
+pub fn read_collection(name: &str) -> (Language, Vec<String>) {
+    let mut language = Language::De;
     let mut categories = Vec::new();
-    for line in read_to_string(format!("src/categories/{name}_edition.txt")).unwrap().lines() {
-        categories.push(line.to_string());
+    for line in read_to_string(path(name)).unwrap().lines() {
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(code) = comment.trim().strip_prefix("language:") {
+                language = code.trim().parse().unwrap_or(language);
+            }
+        } else if !line.trim().is_empty() {
+            categories.push(line.to_string());
+        }
     }
-    categories
+    (language, categories)
+}
+
+/// Language the categories of collection `name` are written in
+pub fn collection_language(name: &str) -> Language {
+    read_collection(name).0
 }
 
-pub fn add_category(filename: &str, category: &str) {
+/// Categories of collection `name` in `language`, or as written if `None`.
+/// Translations are read from `{name}_edition.{language}.toml`, which maps each category to its
+/// translation; categories without one are left out. If there are no translations into
+/// `language` at all, the categories are used as written.
+pub fn load_categories(name: &str, language: Option<Language>) -> Vec<String> {
+    let (written, categories) = read_collection(name);
+    let Some(language) = language.filter(|l| *l != written) else {
+        return categories;
+    };
+    let path = translations_path(name, language);
+    let Ok(content) = read_to_string(&path) else {
+        return categories;
+    };
+    match toml::from_str::<HashMap<String, String>>(&content) {
+        Ok(translations) => categories.iter()
+            .filter_map(|category| translations.get(category).cloned())
+            .collect(),
+        Err(e) => {
+            eprintln!("Couldn't read the translations {path}: {}", e);
+            categories
+        }
+    }
+}
+
+/// Translations of collection `name` into `language`, empty if there are none
+pub fn read_translations(name: &str, language: Language) -> BTreeMap<String, String> {
+    let path = translations_path(name, language);
+    let Ok(content) = read_to_string(&path) else {
+        return BTreeMap::new();
+    };
+    toml::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Couldn't read the translations {path}: {}", e);
+        BTreeMap::new()
+    })
+}
+
+/// Categories of collections `names` in `language`, mapped to the categories as written.
+/// Categories played as written aren't in the map.
+pub fn original_categories(names: &[String], language: Option<Language>) -> HashMap<String, String> {
+    let Some(language) = language else {
+        return HashMap::new();
+    };
+    names.iter()
+        .filter(|name| collection_language(name) != language)
+        .flat_map(|name| read_translations(name, language))
+        .map(|(original, translation)| (translation, original))
+        .collect()
+}
+
+/// Write collection `name` with its `categories` written in `language` and their
+/// `translations` by language, replacing the files of a collection with the same name
+pub fn write_collection(name: &str, language: Language, categories: &[String], translations: &BTreeMap<Language, BTreeMap<String, String>>) -> io::Result<()> {
+    fs::create_dir_all(DIRECTORY)?;
+    let mut content = format!("# language: {}\n", language.code());
+    for category in categories {
+        content.push_str(category);
+        content.push('\n');
+    }
+    fs::write(path(name), content)?;
+    for other in Language::ALL {
+        let path = translations_path(name, other);
+        match translations.get(&other).filter(|t| !t.is_empty()) {
+            Some(translations) => fs::write(&path, toml::to_string(translations).unwrap())?,
+            None => match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Append `category` to the file of collection `name`
+pub fn add_category(name: &str, category: &str) {
     let mut file = OpenOptions::new()
         .read(true)
-        .write(true)
         .append(true)
-        .open(filename)
+        .open(path(name))
         .unwrap();
     if let Err(e) = writeln!(file, "{}", category) {
         eprintln!("Couldn't write to file: {}", e);
     }
 }
 
-pub fn choose_collections() -> Vec<String> {
-    println!("Please choose up to three category collections:");
-    println!("- default");
-    println!("- junior");
-    println!("- adult");
+/// Let the user choose up to three collections, returning their names.
+pub fn choose_collections(lang: Language) -> Vec<String> {
+    println!("{}", lang.t("choose_collections"));
+    for name in collections() {
+        println!("- {name}");
+    }
     let mut category_collections = Vec::new();
     for _ in 0..3 {
         let mut collection = String::new();
         io::stdin()
             .read_line(&mut collection)
             .expect("Failed to read line");
-        if collection.trim() == "default" {
-            let default_categories = load_categories(collection.trim());
-            category_collections.extend(default_categories.clone());
-        }
-        if collection.trim() == "junior" {
-            let junior_categories = load_categories(collection.trim());
-            category_collections.extend(junior_categories.clone());
-        }
-        if collection.trim() == "adult" {
-            let adult_categories = load_categories(collection.trim());
-            category_collections.extend(adult_categories.clone());
-        }
         if collection == "\n" {
             break
         }
+        let name = collection.trim();
+        if is_collection(name) {
+            category_collections.push(name.to_string());
+        }
     }
-    if category_collections.len() == 0 {
-        panic!("Please choose at least one category collection...");
+    if category_collections.is_empty() {
+        panic!("{}", lang.t("choose_at_least_one"));
     }
-    category_collections.clone()
+    category_collections
 }
 
-/// Draw a card with `num` categories from `category_collection`.
-pub fn draw_card(category_collection: &Vec<String>, num: u32) -> Vec<String> {
-    let mut rng = rand::thread_rng();
+/// Draw a card with `num` different categories from `category_collection`.
+/// The card gets smaller if the collection doesn't contain enough different categories.
+pub fn draw_card(category_collection: &[String], num: u32, rng: &mut impl Rng) -> Vec<String> {
+    let mut distinct = category_collection.to_vec();
+    distinct.sort();
+    distinct.dedup();
     let mut card: Vec<String> = Vec::new();
-    for _ in 0..num {
-        let mut category = category_collection.choose(&mut rng).unwrap();
-        while card.contains(&category) {
-            category = category_collection.choose(&mut rng).unwrap();
+    for _ in 0..(num as usize).min(distinct.len()) {
+        let mut category = category_collection.choose(rng).unwrap();
+        while card.contains(category) {
+            category = category_collection.choose(rng).unwrap();
         }
         card.push(category.clone());
     }
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+use crate::background;
+use crate::dice;
+use crate::i18n::Language;
+
+/// House rules of the game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    /// Number of categories on a card
+    pub card_size: u32,
+    /// Smallest timeout that can be entered
+    pub timeout_min: u32,
+    /// Largest timeout that can be entered
+    pub timeout_max: u32,
+    /// Timeout that is suggested when starting a round
+    pub default_timeout: Option<u32>,
+    /// Sides of the dice, `⚡` lets the players choose their own letter.
+    /// Without them the dice matching the language of the categories is used.
+    pub dice: Option<Vec<String>>,
+    /// Collections a new game starts with
+    pub default_collections: Vec<String>,
+}
+
+impl Rules {
+    /// Sides of the dice for categories in `language`
+    pub fn dice(&self, language: Option<Language>) -> Vec<String> {
+        match &self.dice {
+            Some(dice) => dice.clone(),
+            None => dice::dice(language).iter().map(|side| side.to_string()).collect(),
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules
+            { card_size: 6
+            , timeout_min: 1
+            , timeout_max: 999
+            , default_timeout: None
+            , dice: None
+            , default_collections: Vec::new()
+            }
+    }
+}
+
+/// Where the web app listens
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Server {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Read the templates again for every page, to see changes without a restart
+    pub dev: bool,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server { bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED), port: 3333, dev: false }
+    }
+}
+
+/// Look of the web app
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+    /// Black on white without the background image, like printed pages
+    Print,
+}
+
+/// Preferences for what is displayed
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Ui {
+    /// Show the timeline of Success/Next actions with the results of a round
+    pub show_timeline: bool,
+    /// Play sounds: ticks in the last 5 seconds, a buzzer when the time is over and a chime
+    /// on success. The terminal rings its bell instead.
+    pub sounds: bool,
+    /// Language of the texts, otherwise English in the terminal and the language preferred
+    /// by the browser in the web app
+    pub language: Option<Language>,
+    /// Directory with templates that replace the built-in ones of the web app, e.g. the
+    /// `layout.jinja` with the styles of the group
+    pub templates: Option<PathBuf>,
+    /// Theme of devices that didn't choose one
+    pub theme: Theme,
+    /// Background image of games without an uploaded one, instead of the built-in image
+    pub background: Option<PathBuf>,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Ui
+            { show_timeline: true
+            , sounds: true
+            , language: None
+            , templates: None
+            , theme: Theme::Light
+            , background: None
+            }
+    }
+}
+
+/// Contents of `slv.toml`, every value is optional
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rules: Rules,
+    pub server: Server,
+    pub ui: Ui,
+}
+
+impl Config {
+    /// Load the config from `path`. A missing file is only an error if it was `required`,
+    /// otherwise the defaults are used.
+    pub fn load(path: &Path, required: bool) -> Result<Config, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) if !required && !path.exists() => return Ok(Config::default()),
+            Err(e) => return Err(format!("Couldn't read config {}: {e}", path.display())),
+        };
+        let config: Config = toml::from_str(&content)
+            .map_err(|e| format!("Invalid config {}: {e}", path.display()))?;
+        config.check().map_err(|e| format!("Invalid config {}: {e}", path.display()))?;
+        Ok(config)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let rules = &self.rules;
+        if rules.card_size == 0 {
+            return Err("`card_size` must be at least 1".to_string());
+        }
+        if rules.timeout_min == 0 || rules.timeout_min > rules.timeout_max {
+            return Err("`timeout_min` must be at least 1 and not larger than `timeout_max`".to_string());
+        }
+        if let Some(timeout) = rules.default_timeout {
+            if !(rules.timeout_min..=rules.timeout_max).contains(&timeout) {
+                return Err("`default_timeout` must be between `timeout_min` and `timeout_max`".to_string());
+            }
+        }
+        if let Some(dice) = &rules.dice {
+            if dice.is_empty() || dice.iter().any(|side| side.chars().count() != 1) {
+                return Err("`dice` must contain at least one side and every side must be a single letter".to_string());
+            }
+        }
+        if let Some(background) = &self.ui.background {
+            if background::content_type(background).is_none() {
+                return Err("`background` must be a PNG, JPEG, WebP or GIF image".to_string());
+            }
+            if !background.is_file() {
+                return Err(format!("`background` {} doesn't exist", background.display()));
+            }
+        }
+        crate::check_collections(self.ui.language.unwrap_or_default(), &rules.default_collections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(content: &str) -> Result<(), String> {
+        toml::from_str::<Config>(content).map_err(|e| e.to_string())?.check()
+    }
+
+    #[test]
+    fn accepts_the_defaults_and_valid_rules() {
+        assert_eq!(Config::default().check(), Ok(()));
+        assert_eq!(check("[rules]\ncard_size = 4\ntimeout_min = 10\ntimeout_max = 90\ndefault_timeout = 60\ndice = [\"A\", \"⚡\"]\ndefault_collections = [\"default\"]"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(check("[rules]\ncard_size = 0").unwrap_err().contains("card_size"));
+        assert!(check("[rules]\ntimeout_min = 0").unwrap_err().contains("timeout_min"));
+        assert!(check("[rules]\ntimeout_min = 100\ntimeout_max = 50").unwrap_err().contains("timeout_min"));
+        assert!(check("[rules]\ntimeout_max = 50\ndefault_timeout = 60").unwrap_err().contains("default_timeout"));
+        assert!(check("[rules]\ndice = []").unwrap_err().contains("dice"));
+        assert!(check("[rules]\ndice = [\"AB\"]").unwrap_err().contains("dice"));
+        assert!(check("[rules]\ndefault_collections = [\"nope\"]").unwrap_err().contains("'nope'"));
+    }
+
+    #[test]
+    fn rejects_a_background_that_is_no_image() {
+        assert!(check("[ui]\nbackground = \"background.txt\"").unwrap_err().contains("background"));
+        assert!(check("[ui]\nbackground = \"missing.png\"").unwrap_err().contains("doesn't exist"));
+    }
+
+    #[test]
+    fn a_missing_config_is_only_an_error_if_it_was_required() {
+        let path = Path::new("missing/slv.toml");
+        assert!(Config::load(path, false).is_ok());
+        assert!(Config::load(path, true).unwrap_err().contains("Couldn't read config"));
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        assert!(check("[rules]\ncardsize = 4").is_err());
+        assert!(check("[server]\nport = \"high\"").is_err());
+    }
+}
@@ -1,9 +1,12 @@
+use rand::Rng;
 use rand::seq::SliceRandom;
 
+use crate::i18n::Language;
+
 /// Special 30 sided dice from the game.
 /// Is missing X and Y, therefore A, E, O and S are doubled.
 /// Has two wildcard (⚡) sides as well.
-const DICE: [&str; 30] =
+pub const DICE: [&str; 30] =
     [ "A"
     , "A" // no error, is twice on the dice
     , "B"
@@ -36,7 +39,50 @@ const DICE: [&str; 30] =
     , "⚡" // no error, is twice on the dice
     ];
 
-pub fn roll_dice() -> &'static str {
-    let mut rng = rand::thread_rng();
-    DICE.choose(&mut rng).unwrap()
+/// Dice for English categories, which need X and Y too.
+/// Has all 26 letters, E and S doubled and two wildcard (⚡) sides.
+pub const DICE_EN: [&str; 30] =
+    [ "A"
+    , "B"
+    , "C"
+    , "D"
+    , "E"
+    , "E" // no error, is twice on the dice
+    , "F"
+    , "G"
+    , "H"
+    , "I"
+    , "J"
+    , "K"
+    , "L"
+    , "M"
+    , "N"
+    , "O"
+    , "P"
+    , "Q"
+    , "R"
+    , "S"
+    , "S" // no error, is twice on the dice
+    , "T"
+    , "U"
+    , "V"
+    , "W"
+    , "X"
+    , "Y"
+    , "Z"
+    , "⚡" // choose your own letter
+    , "⚡" // no error, is twice on the dice
+    ];
+
+/// Dice matching categories in `language`, the German dice of the game if `None`
+pub fn dice(language: Option<Language>) -> &'static [&'static str] {
+    match language {
+        Some(Language::En) => &DICE_EN,
+        Some(Language::De) | None => &DICE,
+    }
+}
+
+/// Roll a dice with the given sides, e.g. the sides of `DICE`
+pub fn roll_dice<'a>(dice: &'a [String], rng: &mut impl Rng) -> &'a str {
+    dice.choose(rng).unwrap()
 }
@@ -0,0 +1,554 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::mem;
+
+use crate::cards;
+use crate::i18n::Language;
+use crate::round::Round;
+
+/// Phase of the game. Each round walks through
+/// `Setup` → `Rolled` → `Running` → `Finished` and starts over with the next roll.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Phase {
+    /// Waiting for the dice to be rolled
+    Setup,
+    /// Letter is known, waiting for the timeout to start the round
+    Rolled { letter: char },
+    /// Timer is running and categories are played
+    Running { letter: char, timeout: u32, remaining: u32, round: Round },
+    /// Time is over, every category was solved or the round was stopped
+    Finished { letter: char, timeout: u32, remaining: u32, round: Round },
+}
+
+impl Phase {
+    /// Name of the phase as used in the JSON API: `setup`, `rolled`, `running` or `finished`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Setup => "setup",
+            Phase::Rolled { .. } => "rolled",
+            Phase::Running { .. } => "running",
+            Phase::Finished { .. } => "finished",
+        }
+    }
+}
+
+/// Transitions that are not allowed in the current phase
+#[derive(Debug, PartialEq)]
+pub enum GameError {
+    /// No category collections were added yet
+    NoCategories,
+    /// A round can only be started after rolling the dice
+    NotRolled,
+    /// Success/Next/Stop are only allowed while the round is running
+    NotRunning,
+    /// Categories can't be changed while the round is running
+    RoundRunning,
+    /// The timeout is outside of the bounds of the house rules
+    InvalidTimeout { min: u32, max: u32 },
+    /// There is no collection with this name
+    UnknownCollection(String),
+    /// Only the player whose turn it is may control the round
+    NotYourTurn(String),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::NoCategories => write!(f, "Please choose at least one category collection first"),
+            GameError::NotRolled => write!(f, "Please roll the dice before starting the round"),
+            GameError::NotRunning => write!(f, "There is no running round"),
+            GameError::RoundRunning => write!(f, "The current round is still running"),
+            GameError::InvalidTimeout { min, max } => write!(f, "Please provide a timeout between {min}s and {max}s"),
+            GameError::UnknownCollection(name) => write!(f, "There is no collection named '{name}'"),
+            GameError::NotYourTurn(player) => write!(f, "It's {player}'s turn"),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// How urgent the time left in a round is, used to color the progress bars of the timers
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeLevel {
+    /// More than half of the time is left
+    Plenty,
+    /// At most half of the time is left
+    Low,
+    /// The last fifth of the time or the last 5 seconds
+    Critical,
+}
+
+impl TimeLevel {
+    /// Level of a round with `remaining` of `timeout` seconds left
+    pub fn new(remaining: u32, timeout: u32) -> TimeLevel {
+        if remaining <= 5 || remaining * 5 <= timeout {
+            TimeLevel::Critical
+        } else if remaining * 2 <= timeout {
+            TimeLevel::Low
+        } else {
+            TimeLevel::Plenty
+        }
+    }
+}
+
+/// A round that went from `Running` to `Finished`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FinishedRound {
+    pub player: Option<String>,
+    pub letter: char,
+    pub timeout: u32,
+    pub round: Round,
+}
+
+/// UI-independent game: the chosen collections, players and the phase of the current round.
+/// Randomness (dice, cards) is passed in, so every transition is deterministic.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Game {
+    id: String,
+    started: String,
+    collections: Vec<String>,
+    /// Language the categories are played in, `None` for the language they are written in
+    #[serde(default)]
+    language: Option<Language>,
+    categories: Vec<String>,
+    players: Vec<String>,
+    rounds_played: usize,
+    phase: Phase,
+    finished: Vec<FinishedRound>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    /// Create a game without categories, identified by the time it was created
+    pub fn new() -> Game {
+        let now = Local::now();
+        Game
+            { id: now.format("%Y%m%d-%H%M%S%3f").to_string()
+            , started: now.format("%Y-%m-%d %H:%M").to_string()
+            , collections: Vec::new()
+            , language: None
+            , categories: Vec::new()
+            , players: Vec::new()
+            , rounds_played: 0
+            , phase: Phase::Setup
+            , finished: Vec::new()
+            }
+    }
+
+    /// Create a game with the categories of collections `names` in `language`, or as
+    /// written if `None`
+    pub fn with_collections(language: Option<Language>, names: &[String]) -> Game {
+        Game
+            { collections: names.to_vec()
+            , language
+            , categories: names.iter().flat_map(|name| cards::load_categories(name, language)).collect()
+            , ..Game::new()
+            }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn started(&self) -> &str {
+        &self.started
+    }
+
+    pub fn language(&self) -> Option<Language> {
+        self.language
+    }
+
+    pub fn collections(&self) -> &Vec<String> {
+        &self.collections
+    }
+
+    pub fn categories(&self) -> &Vec<String> {
+        &self.categories
+    }
+
+    pub fn players(&self) -> &Vec<String> {
+        &self.players
+    }
+
+    /// Player whose turn it is, taking turns in the order the players were entered
+    pub fn current_player(&self) -> Option<&String> {
+        if self.players.is_empty() {
+            return None;
+        }
+        self.players.get(self.rounds_played % self.players.len())
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// Rolled letter of the current round, if any
+    pub fn letter(&self) -> Option<char> {
+        match self.phase {
+            Phase::Setup => None,
+            Phase::Rolled { letter }
+            | Phase::Running { letter, .. }
+            | Phase::Finished { letter, .. } => Some(letter),
+        }
+    }
+
+    /// Timeout the current round was started with, if any
+    pub fn timeout(&self) -> Option<u32> {
+        match self.phase {
+            Phase::Running { timeout, .. } | Phase::Finished { timeout, .. } => Some(timeout),
+            _ => None,
+        }
+    }
+
+    /// Seconds left in the current round, if any
+    pub fn remaining(&self) -> Option<u32> {
+        match self.phase {
+            Phase::Running { remaining, .. } | Phase::Finished { remaining, .. } => Some(remaining),
+            _ => None,
+        }
+    }
+
+    /// Share of the time left in the current round, from 1.0 at its start down to 0.0
+    pub fn remaining_fraction(&self) -> Option<f64> {
+        match (self.remaining(), self.timeout()) {
+            (Some(remaining), Some(timeout)) if timeout > 0 => Some(remaining as f64 / timeout as f64),
+            _ => None,
+        }
+    }
+
+    /// How urgent the time left in the current round is, if any
+    pub fn time_level(&self) -> Option<TimeLevel> {
+        Some(TimeLevel::new(self.remaining()?, self.timeout()?))
+    }
+
+    /// Card of the current round, if any
+    pub fn round(&self) -> Option<&Round> {
+        match &self.phase {
+            Phase::Running { round, .. } | Phase::Finished { round, .. } => Some(round),
+            _ => None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.phase, Phase::Running { .. })
+    }
+
+    /// Player of the running or finished round
+    pub fn round_player(&self) -> Option<&String> {
+        match self.phase {
+            Phase::Running { .. } => self.current_player(),
+            Phase::Finished { .. } if !self.players.is_empty() => {
+                let n = self.players.len();
+                self.players.get((self.rounds_played + n - 1) % n)
+            },
+            _ => None,
+        }
+    }
+
+    /// Check that `player` may roll, start and play the current round. Only the current player
+    /// may, devices without a player (e.g. one shared by everyone) can control every turn.
+    pub fn check_turn(&self, player: Option<&str>) -> Result<(), GameError> {
+        match (player, self.current_player()) {
+            (Some(player), Some(current)) if player != current => Err(GameError::NotYourTurn(current.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Token of the current step of the round, it changes with every transition but not with
+    /// the passing time. Forms send it back, so a submission that is repeated (browser refresh,
+    /// double click, a second device) is not applied twice.
+    pub fn round_token(&self) -> String {
+        let (step, events) = match &self.phase {
+            Phase::Setup => ("setup", 0),
+            Phase::Rolled { .. } => ("rolled", 0),
+            Phase::Running { round, .. } => ("running", round.events().len()),
+            Phase::Finished { round, .. } => ("finished", round.events().len()),
+        };
+        format!("{}-{}-{step}-{events}", self.id, self.rounds_played)
+    }
+
+    /// Take the rounds that finished since the last call, so each one is recorded exactly once
+    /// no matter which transition finished it.
+    pub fn take_finished(&mut self) -> Vec<FinishedRound> {
+        mem::take(&mut self.finished)
+    }
+
+    /// Add the categories of collection `name` to the game. A collection that is in the game
+    /// already isn't added again, so submitting it twice doesn't double its categories.
+    pub fn add_collection(&mut self, name: &str, categories: Vec<String>) -> Result<(), GameError> {
+        if self.is_running() {
+            return Err(GameError::RoundRunning);
+        }
+        if self.collections.iter().any(|collection| collection == name) {
+            return Ok(());
+        }
+        self.collections.push(name.to_string());
+        self.categories.extend(categories);
+        Ok(())
+    }
+
+    /// Play the categories in `language`, replacing them by `categories`: the ones of all
+    /// collections of the game in that language
+    pub fn set_language(&mut self, language: Option<Language>, categories: Vec<String>) -> Result<(), GameError> {
+        if self.is_running() {
+            return Err(GameError::RoundRunning);
+        }
+        self.language = language;
+        self.categories = categories;
+        Ok(())
+    }
+
+    /// Set the players, who take turns in the given order
+    pub fn set_players(&mut self, players: Vec<String>) -> Result<(), GameError> {
+        if self.is_running() {
+            return Err(GameError::RoundRunning);
+        }
+        self.players = players;
+        Ok(())
+    }
+
+    /// Start over with `game`, dropping all categories and players. Not while a round runs,
+    /// since it would never be recorded.
+    pub fn start_over(&mut self, game: Game) -> Result<(), GameError> {
+        if self.is_running() {
+            return Err(GameError::RoundRunning);
+        }
+        *self = game;
+        Ok(())
+    }
+
+    /// Drop the current round and wait for a new roll
+    pub fn reset_round(&mut self) {
+        self.phase = Phase::Setup;
+    }
+
+    /// Roll the dice. Rolling again before the round started keeps the first letter.
+    pub fn roll(&mut self, letter: char) -> Result<char, GameError> {
+        match self.phase {
+            Phase::Rolled { letter } => Ok(letter),
+            Phase::Running { .. } => Err(GameError::RoundRunning),
+            Phase::Setup | Phase::Finished { .. } => {
+                if self.categories.is_empty() {
+                    return Err(GameError::NoCategories);
+                }
+                self.phase = Phase::Rolled { letter };
+                Ok(letter)
+            },
+        }
+    }
+
+    /// Start the timed round with the given `card`
+    pub fn start(&mut self, timeout: u32, card: Vec<String>) -> Result<(), GameError> {
+        match self.phase {
+            Phase::Rolled { letter } => {
+                self.phase = Phase::Running { letter, timeout, remaining: timeout, round: Round::new(card) };
+                Ok(())
+            },
+            Phase::Running { .. } => Err(GameError::RoundRunning),
+            _ => Err(GameError::NotRolled),
+        }
+    }
+
+    /// The current category was solved
+    pub fn success(&mut self) -> Result<(), GameError> {
+        self.update_round(Round::success)
+    }
+
+    /// The current category was skipped
+    pub fn skip(&mut self) -> Result<(), GameError> {
+        self.update_round(Round::next)
+    }
+
+    /// One second passed. Finishes the round when the time is over.
+    pub fn tick(&mut self) {
+        if let Phase::Running { remaining, .. } = &mut self.phase {
+            *remaining = remaining.saturating_sub(1);
+            if *remaining == 0 {
+                self.finish();
+            }
+        }
+    }
+
+    /// Stop the running round. Does nothing if no round is running.
+    pub fn finish(&mut self) {
+        if !self.is_running() {
+            return;
+        }
+        if let Phase::Running { letter, timeout, remaining, round } = mem::replace(&mut self.phase, Phase::Setup) {
+            let player = self.current_player().cloned();
+            self.finished.push(FinishedRound { player, letter, timeout, round: round.clone() });
+            self.rounds_played += 1;
+            self.phase = Phase::Finished { letter, timeout, remaining, round };
+        }
+    }
+
+    /// Apply `action` to the running round, passing the second of the round it happens in
+    fn update_round(&mut self, action: impl FnOnce(&mut Round, u32)) -> Result<(), GameError> {
+        match &mut self.phase {
+            Phase::Running { round, timeout, remaining, .. } => {
+                action(round, *timeout - *remaining);
+                if round.is_finished() {
+                    self.finish();
+                }
+                Ok(())
+            },
+            _ => Err(GameError::NotRunning),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories() -> Vec<String> {
+        ["A", "B", "C"].iter().map(|c| c.to_string()).collect()
+    }
+
+    fn game() -> Game {
+        let mut game = Game::new();
+        game.add_collection("default", categories()).unwrap();
+        game
+    }
+
+    fn running(timeout: u32) -> Game {
+        let mut game = game();
+        game.roll('B').unwrap();
+        game.start(timeout, categories()).unwrap();
+        game
+    }
+
+    #[test]
+    fn walks_through_the_phases() {
+        let mut game = game();
+        assert!(matches!(game.phase(), Phase::Setup));
+        assert_eq!(game.roll('B'), Ok('B'));
+        assert!(matches!(game.phase(), Phase::Rolled { letter: 'B' }));
+        // rolling again keeps the letter
+        assert_eq!(game.roll('X'), Ok('B'));
+        game.start(60, categories()).unwrap();
+        assert!(game.is_running());
+        assert_eq!(game.remaining(), Some(60));
+        game.success().unwrap();
+        game.skip().unwrap();
+        game.finish();
+        assert!(matches!(game.phase(), Phase::Finished { .. }));
+        assert_eq!(game.take_finished().len(), 1);
+        assert!(game.take_finished().is_empty());
+        assert_eq!(game.roll('C'), Ok('C'));
+    }
+
+    #[test]
+    fn rejects_transitions_of_other_phases() {
+        let mut game = Game::new();
+        assert_eq!(game.roll('B'), Err(GameError::NoCategories));
+        let mut game = self::game();
+        assert_eq!(game.success(), Err(GameError::NotRunning));
+        assert_eq!(game.skip(), Err(GameError::NotRunning));
+        assert_eq!(game.start(60, categories()), Err(GameError::NotRolled));
+
+        let mut game = running(60);
+        assert_eq!(game.add_collection("junior", categories()), Err(GameError::RoundRunning));
+        assert_eq!(game.set_players(vec!["Anna".to_string()]), Err(GameError::RoundRunning));
+        assert_eq!(game.set_language(Some(Language::En), categories()), Err(GameError::RoundRunning));
+        assert_eq!(game.roll('C'), Err(GameError::RoundRunning));
+        assert_eq!(game.start(60, categories()), Err(GameError::RoundRunning));
+        assert_eq!(game.start_over(Game::new()), Err(GameError::RoundRunning));
+        game.finish();
+        assert_eq!(game.start_over(Game::new()), Ok(()));
+        assert!(game.categories().is_empty());
+        let mut game = running(60);
+        game.finish();
+        assert_eq!(game.start(60, categories()), Err(GameError::NotRolled));
+    }
+
+    #[test]
+    fn solving_every_category_finishes_the_round() {
+        let mut game = running(60);
+        for _ in 0..3 {
+            game.success().unwrap();
+        }
+        assert!(matches!(game.phase(), Phase::Finished { .. }));
+    }
+
+    #[test]
+    fn round_token_changes_with_every_step() {
+        let mut game = game();
+        let mut tokens = vec![game.round_token()];
+        game.roll('B').unwrap();
+        tokens.push(game.round_token());
+        game.start(60, categories()).unwrap();
+        tokens.push(game.round_token());
+        game.tick();
+        // the passing time doesn't change the step
+        assert_eq!(game.round_token(), *tokens.last().unwrap());
+        game.skip().unwrap();
+        tokens.push(game.round_token());
+        game.success().unwrap();
+        tokens.push(game.round_token());
+        game.finish();
+        tokens.push(game.round_token());
+        game.roll('C').unwrap();
+        tokens.push(game.round_token());
+        let mut distinct = tokens.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), tokens.len(), "{tokens:?}");
+    }
+
+    #[test]
+    fn tick_finishes_the_round_at_zero() {
+        let mut game = running(2);
+        game.tick();
+        assert!(game.is_running());
+        assert_eq!(game.remaining(), Some(1));
+        game.tick();
+        assert!(matches!(game.phase(), Phase::Finished { remaining: 0, .. }));
+        assert_eq!(game.take_finished().len(), 1);
+        // ticks after the round are ignored
+        game.tick();
+        assert!(game.take_finished().is_empty());
+    }
+
+    #[test]
+    fn adding_a_collection_again_changes_nothing() {
+        let mut game = game();
+        game.add_collection("default", categories()).unwrap();
+        assert_eq!(game.collections(), &vec!["default".to_string()]);
+        assert_eq!(game.categories(), &categories());
+    }
+
+    #[test]
+    fn players_take_turns() {
+        let mut game = game();
+        game.set_players(vec!["Anna".to_string(), "Ben".to_string()]).unwrap();
+        assert_eq!(game.check_turn(Some("Ben")), Err(GameError::NotYourTurn("Anna".to_string())));
+        assert_eq!(game.check_turn(None), Ok(()));
+        game.roll('B').unwrap();
+        game.start(60, categories()).unwrap();
+        game.finish();
+        assert_eq!(game.round_player().unwrap(), "Anna");
+        assert_eq!(game.current_player().unwrap(), "Ben");
+    }
+
+    #[test]
+    fn time_level_thresholds() {
+        assert_eq!(TimeLevel::new(60, 60), TimeLevel::Plenty);
+        assert_eq!(TimeLevel::new(31, 60), TimeLevel::Plenty);
+        assert_eq!(TimeLevel::new(30, 60), TimeLevel::Low);
+        assert_eq!(TimeLevel::new(13, 60), TimeLevel::Low);
+        assert_eq!(TimeLevel::new(12, 60), TimeLevel::Critical);
+        // the last 5 seconds are critical even in long rounds
+        assert_eq!(TimeLevel::new(5, 20), TimeLevel::Critical);
+        assert_eq!(TimeLevel::new(5, 8), TimeLevel::Critical);
+        assert_eq!(TimeLevel::new(7, 10), TimeLevel::Plenty);
+        assert_eq!(TimeLevel::new(0, 60), TimeLevel::Critical);
+    }
+}
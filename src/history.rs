@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::game::{FinishedRound, Game};
+use crate::print::{Card, Entry, Sheet};
+use crate::round::{Round, RoundEvent};
+
+/// Outcome of one category of a played round
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CategoryRecord {
+    category: String,
+    solved: bool,
+    solve_time: Option<u32>,
+}
+
+/// One played round
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RoundRecord {
+    player: Option<String>,
+    letter: char,
+    timeout: u32,
+    categories: Vec<CategoryRecord>,
+    timeline: Vec<RoundEvent>,
+}
+
+impl RoundRecord {
+    /// Outcome of `round`, played by `player` with the given letter and timeout
+    pub fn new(player: Option<String>, letter: char, timeout: u32, round: &Round) -> RoundRecord {
+        let categories = round.complete_card().iter()
+            .map(|category| CategoryRecord
+                { category: category.clone()
+                , solved: !round.reduced_card().contains(category)
+                , solve_time: round.solve_time(category)
+                })
+            .collect();
+        RoundRecord
+            { player
+            , letter
+            , timeout
+            , categories
+            , timeline: round.events().clone()
+            }
+    }
+
+    fn from_finished(finished: &FinishedRound) -> RoundRecord {
+        Self::new(finished.player.clone(), finished.letter, finished.timeout, &finished.round)
+    }
+
+    fn card(&self) -> Card {
+        let entries = self.categories.iter()
+            .map(|record| Entry::new(record.category.clone(), Some(record.solved), record.solve_time))
+            .collect();
+        Card::played(self.letter, self.player.clone(), self.timeout, entries)
+    }
+}
+
+/// A game with all of its played rounds, stored as one JSON file in `<data dir>/history`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameRecord {
+    id: String,
+    started: String,
+    collections: Vec<String>,
+    players: Vec<String>,
+    rounds: Vec<RoundRecord>,
+}
+
+impl GameRecord {
+    fn directory(data_dir: &Path) -> PathBuf {
+        data_dir.join("history")
+    }
+
+    fn path(data_dir: &Path, id: &str) -> PathBuf {
+        Self::directory(data_dir).join(format!("{id}.json"))
+    }
+
+    /// Load the game with the given `id`, `None` if it doesn't exist or can't be read.
+    pub fn load(data_dir: &Path, id: &str) -> Option<GameRecord> {
+        // ids are generated from timestamps, anything else must not reach the file system
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return None;
+        }
+        let content = fs::read_to_string(Self::path(data_dir, id)).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                eprintln!("Couldn't read game {id}: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Load the latest stored game, `None` if there is none yet
+    pub fn latest(data_dir: &Path) -> Option<GameRecord> {
+        Self::load_all(data_dir).into_iter().next()
+    }
+
+    /// Printable sheet with the letters, cards and results of all rounds
+    pub fn sheet(&self) -> Sheet {
+        let cards = self.rounds.iter().map(RoundRecord::card).collect();
+        Sheet::game(self.started.clone(), self.collections.clone(), self.players.clone(), cards)
+    }
+
+    /// Load all stored games, the latest first
+    pub fn load_all(data_dir: &Path) -> Vec<GameRecord> {
+        let Ok(entries) = fs::read_dir(Self::directory(data_dir)) else {
+            return Vec::new();
+        };
+        let mut records: Vec<GameRecord> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                Self::load(data_dir, name.strip_suffix(".json")?)
+            })
+            .collect();
+        records.sort_by(|a, b| b.id.cmp(&a.id));
+        records
+    }
+
+    /// Save the game to `data_dir`
+    pub fn save(&self, data_dir: &Path) {
+        let result = fs::create_dir_all(Self::directory(data_dir))
+            .and_then(|_| fs::write(Self::path(data_dir, &self.id), serde_json::to_string_pretty(self).unwrap()));
+        if let Err(e) = result {
+            eprintln!("Couldn't write game history: {}", e);
+        }
+    }
+
+    /// Add the finished rounds of `game` to its record stored in `data_dir`
+    pub fn record_rounds(data_dir: &Path, game: &Game, finished: &[FinishedRound]) {
+        if finished.is_empty() {
+            return;
+        }
+        let mut record = Self::load(data_dir, game.id()).unwrap_or_else(|| GameRecord
+            { id: game.id().to_string()
+            , started: game.started().to_string()
+            , collections: Vec::new()
+            , players: Vec::new()
+            , rounds: Vec::new()
+            });
+        // collections and players can change between rounds
+        record.collections = game.collections().clone();
+        record.players = game.players().clone();
+        record.rounds.extend(finished.iter().map(RoundRecord::from_finished));
+        record.save(data_dir);
+    }
+}
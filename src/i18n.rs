@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::bundle::BundleError;
+use crate::game::GameError;
+
+/// Languages of the user interface, and of the categories of collections that are
+/// translated into them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+}
+
+impl Language {
+    /// All supported languages
+    pub const ALL: [Language; 2] = [Language::En, Language::De];
+
+    /// Code of the language, as used in `slv.toml`, on the command line and in HTML
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::De => "de",
+        }
+    }
+
+    fn catalog(self) -> &'static HashMap<String, String> {
+        static EN: OnceLock<HashMap<String, String>> = OnceLock::new();
+        static DE: OnceLock<HashMap<String, String>> = OnceLock::new();
+        // the catalogs are part of the binary, so they can't be invalid at runtime
+        match self {
+            Language::En => EN.get_or_init(|| toml::from_str(include_str!("./locales/en.toml")).unwrap()),
+            Language::De => DE.get_or_init(|| toml::from_str(include_str!("./locales/de.toml")).unwrap()),
+        }
+    }
+
+    /// Text for `key`, falling back to English and then to the key itself if it isn't translated
+    pub fn t(self, key: &str) -> &str {
+        self.catalog().get(key)
+            .or_else(|| Language::En.catalog().get(key))
+            .map(|text| text.as_str())
+            .unwrap_or(key)
+    }
+
+    /// Text for `key` with every `{name}` placeholder replaced by the value of `name` in `args`
+    pub fn format(self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut text = self.t(key).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{name}}}"), &value.to_string());
+        }
+        text
+    }
+
+    /// Text explaining why the `Game` rejected a transition
+    pub fn error(self, error: &GameError) -> String {
+        match error {
+            GameError::NoCategories => self.t("error_no_categories").to_string(),
+            GameError::NotRolled => self.t("error_not_rolled").to_string(),
+            GameError::NotRunning => self.t("error_not_running").to_string(),
+            GameError::RoundRunning => self.t("error_round_running").to_string(),
+            GameError::InvalidTimeout { min, max } => self.format("error_invalid_timeout", &[("min", min), ("max", max)]),
+            GameError::UnknownCollection(name) => self.format("error_unknown_collection", &[("name", name)]),
+            GameError::NotYourTurn(player) => self.format("error_not_your_turn", &[("player", player)]),
+        }
+    }
+
+    /// Reason why a collection bundle couldn't be imported, in this language
+    pub fn bundle_error(self, error: &BundleError) -> String {
+        match error {
+            BundleError::Invalid(reason) => self.format("error_bundle_invalid", &[("reason", reason)]),
+            BundleError::UnsupportedVersion(version) => self.format("error_bundle_version", &[("version", version)]),
+            BundleError::InvalidName(name) => self.format("error_bundle_name", &[("name", name)]),
+            BundleError::Exists(name) => self.format("error_bundle_exists", &[("name", name)]),
+            BundleError::LanguageMismatch { name, existing, imported } => {
+                let args: [(&str, &dyn fmt::Display); 3] = [("name", name), ("existing", existing), ("imported", imported)];
+                self.format("error_bundle_language", &args)
+            },
+            BundleError::Io(e) => self.format("error_bundle_save", &[("error", e)]),
+        }
+    }
+
+    /// Supported language the browser prefers most according to its `Accept-Language` header,
+    /// e.g. `de-DE,de;q=0.9,en;q=0.8`
+    pub fn from_accept_language(header: &str) -> Option<Language> {
+        let mut preferences: Vec<(Language, f32)> = header.split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.trim().split(';');
+                let tag = parts.next()?.trim();
+                let language = tag.split('-').next()?.parse().ok()?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse().ok())?;
+                Some((language, quality))
+            })
+            .collect();
+        // stable, so equally preferred languages keep the order of the header
+        preferences.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        preferences.first().filter(|(_, quality)| *quality > 0.0).map(|(language, _)| *language)
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "en" => Ok(Language::En),
+            "de" => Ok(Language::De),
+            _ => Err(format!("Unknown language '{s}', must be one of: en, de")),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_the_language_with_the_highest_quality() {
+        assert_eq!(Language::from_accept_language("de-DE,de;q=0.9,en;q=0.8"), Some(Language::De));
+        assert_eq!(Language::from_accept_language("en;q=0.4, de;q=0.7"), Some(Language::De));
+        assert_eq!(Language::from_accept_language("EN-us"), Some(Language::En));
+        // equally preferred languages keep the order of the header
+        assert_eq!(Language::from_accept_language("en, de"), Some(Language::En));
+    }
+
+    #[test]
+    fn skips_unsupported_and_unreadable_languages() {
+        assert_eq!(Language::from_accept_language("fr-FR,fr;q=0.9,de;q=0.5"), Some(Language::De));
+        assert_eq!(Language::from_accept_language("de;q=high, en;q=0.2"), Some(Language::En));
+        assert_eq!(Language::from_accept_language("fr, it"), None);
+        assert_eq!(Language::from_accept_language(""), None);
+    }
+
+    #[test]
+    fn rejects_languages_of_quality_zero() {
+        assert_eq!(Language::from_accept_language("de;q=0"), None);
+        assert_eq!(Language::from_accept_language("de;q=0, en;q=0.1"), Some(Language::En));
+    }
+
+    #[test]
+    fn every_text_is_translated() {
+        for key in Language::En.catalog().keys() {
+            assert!(Language::De.catalog().contains_key(key), "{key} is missing in de.toml");
+        }
+        for key in Language::De.catalog().keys() {
+            assert!(Language::En.catalog().contains_key(key), "{key} is missing in en.toml");
+        }
+    }
+}
@@ -1,107 +1,315 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
+mod background;
+pub mod bundle;
 mod cards;
+pub mod config;
 mod dice;
+pub mod game;
+mod history;
+pub mod i18n;
+pub mod print;
+mod round;
+mod snapshot;
+mod statistics;
 mod timer;
 pub mod web_app;
 
+/// Settings shared by the web app and the terminal
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Directory where statistics and other data that outlives a game is stored
+    pub data_dir: PathBuf,
+    /// Seed for dice and cards, random if not set
+    pub seed: Option<u64>,
+    /// House rules and preferences from `slv.toml`
+    pub config: config::Config,
+}
+
+impl Settings {
+    /// Language of the terminal, and of the web app if set in the config or on the command line
+    pub fn language(&self) -> i18n::Language {
+        self.config.ui.language.unwrap_or_default()
+    }
+
+    /// Random number generator for dice and cards, reproducible if a seed is set
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+/// Store the rounds that finished since the last call in the statistics and the game history
+fn record_finished(data_dir: &Path, game: &mut game::Game) {
+    let finished = game.take_finished();
+    // statistics are kept per category as written, whatever language it was played in
+    let originals = cards::original_categories(game.collections(), game.language());
+    statistics::Statistics::record_rounds(data_dir, &finished, &originals);
+    history::GameRecord::record_rounds(data_dir, game, &finished);
+}
+
 /// Wait for user input to continue
-fn wait_for_user() {
+fn wait_for_user(lang: i18n::Language) {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
 
     // We want the cursor to stay at the end of the line,
     // so we print without a newline and flush manually.
-    write!(stdout, "Press Enter (↵) to continue...").unwrap();
+    write!(stdout, "{}", lang.t("press_enter")).unwrap();
     stdout.flush().unwrap();
 
     // Read everything and discard
     let _ = stdin.read_line(&mut String::new()).unwrap();
 }
 
-/// Start one round of the game
-fn start_round(category_collection: &Vec<String>) {
-    println!("Please write down the current timeout:");
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read line");
-    let timeout = timer::TimeoutFromString::new(input);
+/// Start one round of the game. Returns false without playing if the input is closed
+/// before the timeout was entered.
+fn start_round(game: &mut game::Game, settings: &Settings, rng: &mut StdRng) -> bool {
+    let rules = &settings.config.rules;
+    let lang = settings.language();
+    match rules.default_timeout {
+        Some(timeout) => println!("{}", lang.format("timeout_prompt_default", &[("timeout", &timeout)])),
+        None => println!("{}", lang.t("timeout_prompt")),
+    }
+    let timeout = loop {
+        let mut input = String::new();
+        let read = io::stdin()
+            .read_line(&mut input)
+            .expect("Failed to read line");
+        match timer::TimeoutFromString::new(input, rules, lang) {
+            Ok(timeout) => break timeout,
+            // the input is closed, asking again would never end
+            Err(_) if read == 0 => return false,
+            Err(message) => println!("{message}"),
+        }
+    };
     println!();
 
-    let letter = dice::roll_dice();
-    println!("Your letter is: {letter}");
-    wait_for_user();
+    let dice = rules.dice(game.language());
+    let letter = game.roll(dice::roll_dice(&dice, rng).chars().next().unwrap()).unwrap();
+    if let Some(player) = game.current_player() {
+        println!("{}", lang.format("turn", &[("player", player)]));
+    }
+    println!("{}", lang.format("your_letter", &[("letter", &letter)]));
+    wait_for_user(lang);
     println!();
 
-    let card = cards::draw_card(category_collection, 6);
-    println!("Your card contains the following categories:");
-    for category in card {
-        println!("- {category}")
+    let card = cards::draw_card(game.categories(), rules.card_size, rng);
+    game.start(timeout.value(), card).unwrap();
+    timer::start_timer(game, settings.config.ui.sounds, lang);
+    record_finished(&settings.data_dir, game);
+    let round = game.round().unwrap();
+    if round.is_finished() {
+        println!("{}", lang.t("all_solved"));
+    }
+    else {
+        println!("{}", lang.t("time_over"));
     }
     println!();
 
-    timer::start_timer(timeout);
-    println!("Your time is over!");
-    println!()
+    println!("{}", lang.t("card_contained"));
+    for category in round.complete_card() {
+        let mark = if round.reduced_card().contains(category) { "☒" } else { "☑" };
+        println!("{mark} {category}")
+    }
+    println!();
+
+    if !round.events().is_empty() {
+        println!("{}", lang.t("timeline"));
+        for event in round.events() {
+            let action = match event.action {
+                round::Action::Success => lang.t("action_success"),
+                round::Action::Next => lang.t("action_next"),
+            };
+            println!("{:>3}s {action}: {}", event.second, event.category)
+        }
+        println!()
+    }
+    true
 }
 
 /// Add new categories to existing collections.
-pub fn add_categories() {
-    println!("Please choose a category to add to:");
-    println!("- default");
-    println!("- junior");
-    println!("- adult");
+pub fn add_categories(lang: i18n::Language) {
+    println!("{}", lang.t("add_choose"));
+    for name in cards::collections() {
+        println!("- {name}");
+    }
     let mut collection = String::new();
     io::stdin()
         .read_line(&mut collection)
         .expect("Failed to read line");
     println!();
-    let filename = if collection.trim() == "default" {
-        "src/categories/default_edition.txt"
-    }
-    else if collection.trim() == "junior" {
-        "src/categories/junior_edition.txt"
+    let name = collection.trim();
+    if !cards::is_collection(name) {
+        println!("{}", lang.format("add_unknown", &[("name", &name)]));
+        return;
     }
-    else if collection.trim() == "adult" {
-        "src/categories/adult_edition.txt"
-    }
-    else {
-        println!("Unknown category: {}", collection.trim());
-        return ();
-    };
 
     loop {
-        println!("Please input the category you want to add or write 'exit' to return:");
+        println!("{}", lang.t("add_prompt"));
         let mut category = String::new();
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut category)
             .expect("Failed to read line");
-        if category.trim() == "exit" {
-            println!("Returning...");
+        // a closed input ends like "exit", instead of adding empty categories forever
+        if read == 0 || category.trim() == "exit" {
+            println!("{}", lang.t("returning"));
             println!();
             break
         }
         println!();
-        cards::add_category(filename, category.trim());
+        cards::add_category(name, category.trim());
+    }
+}
+
+/// Split a comma separated list of player names
+fn parse_players(input: &str) -> Vec<String> {
+    input.split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Check that every name in `collections` is a known category collection
+pub fn check_collections(lang: i18n::Language, collections: &[String]) -> Result<(), String> {
+    match collections.iter().find(|c| !cards::is_collection(c)) {
+        Some(unknown) => Err(unknown_collection(lang, unknown)),
+        None => Ok(()),
+    }
+}
+
+/// Message for the unknown collection `name`, listing the known ones
+fn unknown_collection(lang: i18n::Language, name: &str) -> String {
+    lang.format("error_unknown_collection_choice", &[("name", &name), ("collections", &cards::collections().join(", "))])
+}
+
+/// Message for an IO `error` with the file at `path`, `key` tells whether it was read or written
+fn file_error(lang: i18n::Language, key: &str, path: &Path, error: io::Error) -> String {
+    lang.format(key, &[("path", &path.display()), ("error", &error)])
+}
+
+/// Print all category collections with their language and the number of categories they contain
+pub fn list_collections(lang: i18n::Language) {
+    for name in cards::collections() {
+        let args: [(&str, &dyn std::fmt::Display); 3] =
+            [ ("name", &name)
+            , ("count", &cards::load_categories(&name, None).len())
+            , ("language", &cards::collection_language(&name))
+            ];
+        println!("{}", lang.format("collection_count", &args));
     }
 }
 
-/// Start the game by choosing category collections
-/// and starting a new round.
-pub fn start_game() {
-    let category_collection = cards::choose_collections();
+/// Write collection `name` as bundle file to `output`, or to the standard output
+pub fn export_collection(lang: i18n::Language, name: &str, output: Option<&Path>) -> Result<(), String> {
+    let bundle = bundle::Bundle::export(name)
+        .ok_or_else(|| unknown_collection(lang, name))?;
+    match output {
+        Some(path) => fs::write(path, bundle.to_json()).map_err(|e| file_error(lang, "error_write_file", path, e)),
+        None => {
+            println!("{}", bundle.to_json());
+            Ok(())
+        },
+    }
+}
+
+/// Import the collection bundle file at `path`, as `name` if given, handling a collection of
+/// the same name as told by `conflict`
+pub fn import_collection(lang: i18n::Language, path: &Path, name: Option<&str>, conflict: bundle::Conflict) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| file_error(lang, "error_read_file", path, e))?;
+    let imported = bundle::Bundle::parse(&content)
+        .and_then(|bundle| bundle.import(name, conflict))
+        .map_err(|e| lang.bundle_error(&e))?;
+    let args: [(&str, &dyn std::fmt::Display); 2] = [("name", &imported.name), ("added", &imported.added)];
+    println!("{}", lang.format("import_done", &args));
+    Ok(())
+}
+
+/// Roll the dice once and print the letter
+pub fn roll(settings: &Settings) {
+    let dice = settings.config.rules.dice(settings.config.ui.language);
+    println!("{}", dice::roll_dice(&dice, &mut settings.rng()));
+}
+
+/// Draw one card from the given `collections` and print its categories
+pub fn draw(settings: &Settings, collections: &[String]) {
+    let categories: Vec<String> = collections.iter()
+        .flat_map(|name| cards::load_categories(name, settings.config.ui.language))
+        .collect();
+    for category in cards::draw_card(&categories, settings.config.rules.card_size, &mut settings.rng()) {
+        println!("{category}");
+    }
+}
+
+/// Printable sheet with the letters, cards and results of the stored game `id`, or of the
+/// latest one for "latest"
+pub fn print_game(settings: &Settings, id: &str, format: print::Format) -> Result<String, String> {
+    let lang = settings.language();
+    let record = if id == "latest" {
+        history::GameRecord::latest(&settings.data_dir)
+    } else {
+        history::GameRecord::load(&settings.data_dir, id)
+    };
+    let record = record.ok_or_else(|| lang.format("error_unknown_game", &[("id", &id)]))?;
+    render_sheet(settings, &record.sheet(), format)
+}
+
+/// `count` random category cards from the given `collections`, to play on paper
+pub fn print_cards(settings: &Settings, collections: &[String], count: u32, format: print::Format) -> Result<String, String> {
+    let sheet = print::Sheet::cards(collections, settings.config.ui.language, count, settings.config.rules.card_size, &mut settings.rng());
+    render_sheet(settings, &sheet, format)
+}
+
+/// `sheet` in `format`, the printable page with the templates of the web app
+fn render_sheet(settings: &Settings, sheet: &print::Sheet, format: print::Format) -> Result<String, String> {
+    let templates = web_app::templates::Templates::new(settings.config.ui.templates.clone(), false, settings.config.ui.theme);
+    let lang = settings.language();
+    web_app::render_sheet(&templates, lang, sheet, format)
+        .map_err(|e| lang.format("error_render_sheet", &[("error", &e)]))
+}
+
+/// Start the game with the given category collections, or let the user choose
+/// them if there are none, and start a new round. The categories are played in the configured
+/// language, or as written if there is none.
+pub fn start_game(settings: &Settings, collections: &[String]) {
+    let mut rng = settings.rng();
+    let lang = settings.language();
+    let chosen = if collections.is_empty() {
+        cards::choose_collections(lang)
+    } else {
+        collections.to_vec()
+    };
+    let mut game = game::Game::with_collections(settings.config.ui.language, &chosen);
+
+    println!("{}", lang.t("players_prompt"));
+    let mut players = String::new();
+    io::stdin()
+        .read_line(&mut players)
+        .expect("Failed to read line");
+    game.set_players(parse_players(&players)).unwrap();
+    println!();
 
     loop {
-        start_round(&category_collection);
-        println!("Do you want to start a new round? (y/n)");
+        if !start_round(&mut game, settings, &mut rng) {
+            println!();
+            println!("{}", lang.t("stopping"));
+            break
+        }
+        println!("{}", lang.t("new_round_prompt"));
         let mut input = String::new();
         io::stdin()
             .read_line(&mut input)
             .expect("Failed to read line");
-        if input.trim() != "y" {
-            println!("Stopping the game...");
+        // "j" for the German "ja"
+        if !["y", "j"].contains(&input.trim()) {
+            println!("{}", lang.t("stopping"));
             println!();
             break
         }
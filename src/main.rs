@@ -1,48 +1,352 @@
-use std::{env, io, process};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-use slv;
+/// Digital helper for the German board game "Stadt Land Vollpfosten".
+/// Serves the web app if no command is given.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Directory for statistics, game history and the active games
+    #[arg(long, global = true, default_value = "data")]
+    data_dir: PathBuf,
+
+    /// Config file with house rules and defaults [default: <DATA_DIR>/slv.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Seed for dice and cards, to get reproducible games
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// Number of categories on a card [default: 6]
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    card_size: Option<u32>,
+
+    /// Language of the texts: en or de [default: the one of the config, otherwise English in
+    /// the terminal and the one preferred by the browser in the web app]
+    #[arg(long, global = true)]
+    language: Option<slv::i18n::Language>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Options of the web app when no command is given
+    #[command(flatten)]
+    webapp: WebappArgs,
+}
+
+impl Cli {
+    /// Parse the arguments, rejecting the options of the web app before a command, like
+    /// `slv --port 9 terminal`, which would be ignored
+    fn parse_checked() -> Cli {
+        let matches = Cli::command().get_matches();
+        if let Some((command, _)) = matches.subcommand() {
+            if let Some(option) = given_webapp_option(&matches) {
+                Cli::command()
+                    .error(ErrorKind::ArgumentConflict, format!("the subcommand '{command}' cannot be used with '--{option}'"))
+                    .exit();
+            }
+        }
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+}
+
+/// First option of the web app given on the command line, options set by the environment
+/// don't count
+fn given_webapp_option(matches: &ArgMatches) -> Option<String> {
+    WebappArgs::augment_args(clap::Command::new("webapp")).get_arguments()
+        .map(|arg| arg.get_id().as_str())
+        .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .map(|id| id.replace('_', "-"))
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the game as web app
+    Webapp(WebappArgs),
+    /// Play the game in the terminal
+    Terminal {
+        /// Comma separated collections to play with, asked for interactively if neither set
+        /// here nor in the config
+        #[arg(long, value_delimiter = ',')]
+        collections: Vec<String>,
+    },
+    /// List the available category collections
+    Collections,
+    /// Roll the dice once
+    Roll,
+    /// Draw one card
+    Draw {
+        /// Comma separated collections to draw from [default: the default collections of the
+        /// config, otherwise "default"]
+        #[arg(long, value_delimiter = ',')]
+        collections: Vec<String>,
+    },
+    /// Export a category collection as bundle file, to share it with another group
+    Export {
+        /// Name of the collection
+        name: String,
+
+        /// File to write the bundle to [default: standard output]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Import a category collection from a bundle file
+    Import {
+        /// Bundle file, as written by the export
+        file: PathBuf,
+
+        /// Name to import the collection as [default: the one of the bundle]
+        #[arg(long)]
+        name: Option<String>,
+
+        /// What to do if a collection of the same name exists: abort, replace, merge (add the
+        /// new categories) or rename (import as <NAME>-2)
+        #[arg(long, default_value = "abort")]
+        on_conflict: slv::bundle::Conflict,
+    },
+    /// Print the score sheet of a stored game, or category cards to play on paper
+    Print {
+        /// Id of the game in the history, or "latest"; random category cards if not given
+        #[arg(long)]
+        game: Option<String>,
+
+        /// Comma separated collections to draw the cards from [default: the default
+        /// collections of the config, otherwise "default"]
+        #[arg(long, value_delimiter = ',', conflicts_with = "game")]
+        collections: Vec<String>,
+
+        /// Number of category cards
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=100), conflicts_with = "game")]
+        cards: u32,
+
+        /// Layout: "html" to print from the browser or save as PDF, or "text"
+        #[arg(long, default_value = "text")]
+        format: slv::print::Format,
+
+        /// File to write the sheet to [default: standard output]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct WebappArgs {
+    /// Address to listen on [default: 0.0.0.0 or the one of the config]
+    #[arg(long, env = "SLV_BIND")]
+    bind: Option<IpAddr>,
+
+    /// Only listen on localhost, so the game can't be reached from other devices
+    #[arg(long, conflicts_with = "bind")]
+    localhost: bool,
+
+    /// Port to listen on [default: 3333 or the one of the config]
+    #[arg(long, short, env = "SLV_PORT")]
+    port: Option<u16>,
+
+    /// Directory with templates that replace the built-in ones [default: the one of the config]
+    #[arg(long, env = "SLV_TEMPLATES")]
+    templates: Option<PathBuf>,
+
+    /// Read the templates again for every page, to see changes without a restart
+    #[arg(long)]
+    dev: bool,
+}
+
+impl WebappArgs {
+    /// Address from the arguments or environment, falling back to the config
+    fn address(&self, server: &slv::config::Server) -> SocketAddr {
+        let bind = if self.localhost { IpAddr::V4(Ipv4Addr::LOCALHOST) } else { self.bind.unwrap_or(server.bind) };
+        SocketAddr::new(bind, self.port.unwrap_or(server.port))
+    }
+
+    /// Override the templates of the config with the ones of the arguments or environment
+    fn apply(&self, config: &mut slv::config::Config) {
+        if self.templates.is_some() {
+            config.ui.templates = self.templates.clone();
+        }
+        if self.dev {
+            config.server.dev = true;
+        }
+    }
+}
 
 #[tokio::main]
-async fn main() {
-    println!("Welcome to the - Stand Land Vollpfosten - helper!");
-    println!();
-
-    let mut args = env::args();
-    args.next(); // ignore file name
-    let modi = match args.next() {
-        Some(arg) => arg,
-        None => "webapp".to_string(),
+async fn main() -> ExitCode {
+    let cli = Cli::parse_checked();
+    let config = match &cli.config {
+        Some(path) => slv::config::Config::load(path, true),
+        None => slv::config::Config::load(&cli.data_dir.join("slv.toml"), false),
+    };
+    let mut config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
     };
+    if let Some(card_size) = cli.card_size {
+        config.rules.card_size = card_size;
+    }
+    if cli.language.is_some() {
+        config.ui.language = cli.language;
+    }
+    let default_collections = config.rules.default_collections.clone();
+    let server = config.server.clone();
+    let mut settings = slv::Settings
+        { data_dir: cli.data_dir
+        , seed: cli.seed
+        , config
+        };
 
-    if modi == "webapp" {
-        slv::web_app::serve().await;
-    }
-    else if modi == "terminal" {
-        loop {
-            println!("Please choose what you want to do:");
-            println!("- add (add categories)");
-            println!("- play (start playing the game)");
-            println!("- exit (stop execution)");
-            let mut action = String::new();
-            io::stdin()
-                .read_line(&mut action)
-                .expect("Failed to read line");
+    match cli.command.unwrap_or(Command::Webapp(cli.webapp)) {
+        Command::Webapp(args) => {
+            args.apply(&mut settings.config);
+            let lang = settings.language();
+            println!("{}", lang.t("welcome"));
+            println!();
+            if let Err(e) = slv::web_app::serve(settings, args.address(&server)).await {
+                eprintln!("{}", lang.format("error_serve", &[("error", &e)]));
+                return ExitCode::FAILURE;
+            }
+        },
+        Command::Terminal { mut collections } => {
+            if collections.is_empty() {
+                collections = default_collections;
+            }
+            if let Err(e) = slv::check_collections(settings.language(), &collections) {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+            println!("{}", settings.language().t("welcome"));
             println!();
-            if action.trim() == "add" {
-                slv::add_categories();
-                continue
+            terminal(&settings, &collections);
+        },
+        Command::Collections => slv::list_collections(settings.language()),
+        Command::Roll => slv::roll(&settings),
+        Command::Draw { mut collections } => {
+            if collections.is_empty() {
+                collections = default_collections;
+            }
+            if collections.is_empty() {
+                collections = vec!["default".to_string()];
+            }
+            if let Err(e) = slv::check_collections(settings.language(), &collections) {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+            slv::draw(&settings, &collections);
+        },
+        Command::Export { name, output } => {
+            if let Err(e) = slv::export_collection(settings.language(), &name, output.as_deref()) {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
             }
-            if action.trim() == "play" {
-                slv::start_game();
-                continue
+        },
+        Command::Import { file, name, on_conflict } => {
+            if let Err(e) = slv::import_collection(settings.language(), &file, name.as_deref(), on_conflict) {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
             }
-            if action.trim() == "exit" {
-                break
+        },
+        Command::Print { game, mut collections, cards, format, output } => {
+            let sheet = match game {
+                Some(id) => slv::print_game(&settings, &id, format),
+                None => {
+                    if collections.is_empty() {
+                        collections = default_collections;
+                    }
+                    if collections.is_empty() {
+                        collections = vec!["default".to_string()];
+                    }
+                    slv::check_collections(settings.language(), &collections)
+                        .and_then(|_| slv::print_cards(&settings, &collections, cards, format))
+                },
+            };
+            let sheet = match sheet {
+                Ok(sheet) => sheet,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            match output {
+                Some(path) => if let Err(e) = fs::write(&path, sheet) {
+                    let args: [(&str, &dyn std::fmt::Display); 2] = [("path", &path.display()), ("error", &e)];
+                    eprintln!("{}", settings.language().format("error_write_file", &args));
+                    return ExitCode::FAILURE;
+                },
+                None => print!("{sheet}"),
             }
+        },
+    }
+    ExitCode::SUCCESS
+}
+
+/// Interactive terminal menu
+fn terminal(settings: &slv::Settings, collections: &[String]) {
+    let lang = settings.language();
+    loop {
+        println!("{}", lang.t("menu_choose"));
+        println!("{}", lang.t("menu_add"));
+        println!("{}", lang.t("menu_play"));
+        println!("{}", lang.t("menu_exit"));
+        let mut action = String::new();
+        let read = io::stdin()
+            .read_line(&mut action)
+            .expect("Failed to read line");
+        println!();
+        // the input is closed, nothing more can be chosen
+        if read == 0 {
+            break
+        }
+        if action.trim() == "add" {
+            slv::add_categories(lang);
+            continue
+        }
+        if action.trim() == "play" {
+            slv::start_game(settings, collections);
+            continue
+        }
+        if action.trim() == "exit" {
+            break
         }
     }
-    else {
-        eprintln!("Unknown input '{}', must be either 'terminal' or 'webapp'.", modi);
-        process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn given(args: &[&str]) -> Option<String> {
+        let matches = Cli::command().try_get_matches_from([&["slv"], args].concat()).unwrap();
+        given_webapp_option(&matches)
+    }
+
+    #[test]
+    fn finds_web_app_options_before_a_command() {
+        assert_eq!(given(&["--port", "9", "terminal"]), Some("port".to_string()));
+        assert_eq!(given(&["--localhost", "print"]), Some("localhost".to_string()));
+        assert_eq!(given(&["--dev", "export", "default"]), Some("dev".to_string()));
+    }
+
+    #[test]
+    fn ignores_global_options_and_options_of_the_web_app_command() {
+        assert_eq!(given(&["--data-dir", "data", "--language", "de", "terminal"]), None);
+        assert_eq!(given(&["webapp", "--port", "9"]), None);
+        assert_eq!(given(&["--port", "9"]), Some("port".to_string()));
+    }
+
+    #[test]
+    fn ignores_options_set_by_the_environment() {
+        std::env::set_var("SLV_BIND", "127.0.0.1");
+        assert_eq!(given(&["roll"]), None);
+        std::env::remove_var("SLV_BIND");
     }
 }
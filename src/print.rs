@@ -0,0 +1,282 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::cards;
+use crate::i18n::Language;
+
+/// Layouts a sheet can be printed in
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Page for the print dialog of the browser, which can also save it as PDF
+    #[default]
+    Html,
+    /// Plain text, e.g. for a receipt printer or a message
+    Text,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "html" => Ok(Format::Html),
+            "text" => Ok(Format::Text),
+            _ => Err(format!("Unknown format '{s}', must be one of: html, text")),
+        }
+    }
+}
+
+/// One category of a card, with its outcome if the card was played
+#[derive(Serialize, Clone, Debug)]
+pub struct Entry {
+    category: String,
+    solved: Option<bool>,
+    solve_time: Option<u32>,
+}
+
+impl Entry {
+    pub fn new(category: String, solved: Option<bool>, solve_time: Option<u32>) -> Entry {
+        Entry { category, solved, solve_time }
+    }
+}
+
+/// One card of a sheet, with letter, player and timeout of the round it was played in
+#[derive(Serialize, Clone, Debug)]
+pub struct Card {
+    letter: Option<char>,
+    player: Option<String>,
+    timeout: Option<u32>,
+    entries: Vec<Entry>,
+    /// Number of solved categories, `None` for a card that wasn't played
+    solved: Option<usize>,
+}
+
+impl Card {
+    /// Card played by `player` with the given letter and timeout
+    pub fn played(letter: char, player: Option<String>, timeout: u32, entries: Vec<Entry>) -> Card {
+        Card
+            { letter: Some(letter)
+            , player
+            , timeout: Some(timeout)
+            , solved: Some(entries.iter().filter(|entry| entry.solved == Some(true)).count())
+            , entries
+            }
+    }
+
+    /// Card to play on paper
+    pub fn blank(categories: Vec<String>) -> Card {
+        Card
+            { letter: None
+            , player: None
+            , timeout: None
+            , entries: categories.into_iter().map(|category| Entry::new(category, None, None)).collect()
+            , solved: None
+            }
+    }
+}
+
+/// Solved categories of one player over all rounds of a game
+#[derive(Serialize, Clone, Debug)]
+pub struct Score {
+    player: String,
+    solved: usize,
+    rounds: usize,
+}
+
+/// Printable sheet: the letters, cards and results of a game, or category cards to play on
+/// paper
+#[derive(Serialize, Clone, Debug)]
+pub struct Sheet {
+    /// Start of the game, `None` for category cards
+    started: Option<String>,
+    collections: Vec<String>,
+    players: Vec<String>,
+    letters: Vec<char>,
+    cards: Vec<Card>,
+    scores: Vec<Score>,
+}
+
+impl Sheet {
+    /// Sheet of the game started at `started` with its played `cards`, the best player first
+    pub fn game(started: String, collections: Vec<String>, players: Vec<String>, cards: Vec<Card>) -> Sheet {
+        let mut scores: Vec<Score> = players.iter()
+            .map(|player| {
+                let played: Vec<&Card> = cards.iter().filter(|card| card.player.as_ref() == Some(player)).collect();
+                Score
+                    { player: player.clone()
+                    , solved: played.iter().filter_map(|card| card.solved).sum()
+                    , rounds: played.len()
+                    }
+            })
+            .collect();
+        // stable, so players with the same score keep their turn order
+        scores.sort_by_key(|score| Reverse(score.solved));
+        Sheet
+            { started: Some(started)
+            , collections
+            , players
+            , letters: cards.iter().filter_map(|card| card.letter).collect()
+            , cards
+            , scores
+            }
+    }
+
+    /// `count` random cards of `card_size` categories from the given `collections`
+    pub fn cards(collections: &[String], language: Option<Language>, count: u32, card_size: u32, rng: &mut impl Rng) -> Sheet {
+        let categories: Vec<String> = collections.iter()
+            .flat_map(|name| cards::load_categories(name, language))
+            .collect();
+        Sheet
+            { started: None
+            , collections: collections.to_vec()
+            , players: Vec::new()
+            , letters: Vec::new()
+            , cards: (0..count).map(|_| Card::blank(cards::draw_card(&categories, card_size, rng))).collect()
+            , scores: Vec::new()
+            }
+    }
+
+    /// Heading of the sheet in `lang`
+    pub fn title(&self, lang: Language) -> String {
+        match &self.started {
+            Some(started) => lang.format("print_title_game", &[("started", started)]),
+            None => lang.t("print_title_cards").to_string(),
+        }
+    }
+
+    /// The sheet as plain text in `lang`, with boxes to tick on the cards that weren't played
+    pub fn text(&self, lang: Language) -> String {
+        let mut text = String::new();
+        let title = self.title(lang);
+        writeln!(text, "{title}").unwrap();
+        writeln!(text, "{}", "=".repeat(title.chars().count())).unwrap();
+        writeln!(text, "{}", lang.format("print_collections", &[("collections", &self.collections.join(", "))])).unwrap();
+        if !self.players.is_empty() {
+            writeln!(text, "{}", lang.format("print_players", &[("players", &self.players.join(", "))])).unwrap();
+        }
+        if !self.letters.is_empty() {
+            let letters: Vec<String> = self.letters.iter().map(char::to_string).collect();
+            writeln!(text, "{}", lang.format("print_letters", &[("letters", &letters.join(" "))])).unwrap();
+        }
+
+        for (index, card) in self.cards.iter().enumerate() {
+            writeln!(text).unwrap();
+            let mut heading = match (card.letter, card.timeout) {
+                (Some(letter), Some(timeout)) => {
+                    let args: [(&str, &dyn std::fmt::Display); 3] =
+                        [ ("index", &(index + 1))
+                        , ("letter", &letter)
+                        , ("timeout", &timeout)
+                        ];
+                    lang.format("print_round", &args)
+                },
+                _ => lang.format("print_card", &[("index", &(index + 1))]),
+            };
+            if let Some(player) = &card.player {
+                heading += &lang.format("print_played_by", &[("player", player)]);
+            }
+            writeln!(text, "{heading}").unwrap();
+            writeln!(text, "{}", "-".repeat(heading.chars().count())).unwrap();
+            if card.letter.is_none() {
+                writeln!(text, "{}", lang.t("print_letter_blank")).unwrap();
+            }
+            for entry in &card.entries {
+                let mark = match entry.solved {
+                    Some(true) => "☑",
+                    Some(false) => "☒",
+                    None => "☐",
+                };
+                match entry.solve_time {
+                    Some(seconds) => writeln!(text, "{mark} {} ({seconds} s)", entry.category),
+                    None => writeln!(text, "{mark} {}", entry.category),
+                }.unwrap();
+            }
+            if let Some(solved) = card.solved {
+                let args: [(&str, &dyn std::fmt::Display); 2] = [("solved", &solved), ("count", &card.entries.len())];
+                writeln!(text, "{}", lang.format("print_solved", &args)).unwrap();
+            }
+        }
+
+        if !self.scores.is_empty() {
+            writeln!(text).unwrap();
+            writeln!(text, "{}", lang.t("print_scores")).unwrap();
+            for score in &self.scores {
+                let args: [(&str, &dyn std::fmt::Display); 3] =
+                    [ ("player", &score.player)
+                    , ("solved", &score.solved)
+                    , ("rounds", &score.rounds)
+                    ];
+                writeln!(text, "{}", lang.format("print_score", &args)).unwrap();
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(letter: char, player: &str, solved: &[bool]) -> Card {
+        let entries = solved.iter().enumerate()
+            .map(|(index, solved)| Entry::new(format!("Category {index}"), Some(*solved), solved.then_some(5)))
+            .collect();
+        Card::played(letter, Some(player.to_string()), 60, entries)
+    }
+
+    fn players(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn scores_put_the_best_player_first_and_keep_the_turn_order_on_a_tie() {
+        let cards = vec![
+            card('A', "Anna", &[true, false]),
+            card('B', "Ben", &[true, true]),
+            card('C', "Cleo", &[false, true]),
+            card('D', "Anna", &[false, false]),
+        ];
+        let sheet = Sheet::game("2026-10-18 20:00".to_string(), vec!["default".to_string()], players(&["Anna", "Ben", "Cleo"]), cards);
+        let scores: Vec<(&str, usize, usize)> = sheet.scores.iter()
+            .map(|score| (score.player.as_str(), score.solved, score.rounds))
+            .collect();
+        assert_eq!(scores, vec![("Ben", 2, 1), ("Anna", 1, 2), ("Cleo", 1, 1)]);
+        assert_eq!(sheet.letters, vec!['A', 'B', 'C', 'D']);
+    }
+
+    #[test]
+    fn text_of_a_game_lists_rounds_outcomes_and_scores() {
+        let sheet = Sheet::game("2026-10-18 20:00".to_string(), vec!["default".to_string()], players(&["Anna"]), vec![card('K', "Anna", &[true, false])]);
+        let text = sheet.text(Language::En);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Score sheet of the game started at 2026-10-18 20:00");
+        assert_eq!(lines[1], "=".repeat(lines[0].chars().count()));
+        assert!(lines.contains(&"Letters: K"));
+        assert!(lines.contains(&"Round 1: letter K, 60 seconds, played by Anna"));
+        assert!(lines.contains(&"☑ Category 0 (5 s)"));
+        assert!(lines.contains(&"☒ Category 1"));
+        assert!(lines.contains(&"1 of 2 solved"));
+        assert_eq!(lines.last(), Some(&"Anna: 1 solved in 1 rounds"));
+    }
+
+    #[test]
+    fn text_of_blank_cards_has_boxes_to_tick() {
+        let sheet = Sheet
+            { started: None
+            , collections: vec!["default".to_string()]
+            , players: Vec::new()
+            , letters: Vec::new()
+            , cards: vec![Card::blank(vec!["Stadt".to_string(), "Land".to_string()])]
+            , scores: Vec::new()
+            };
+        let text = sheet.text(Language::De);
+        assert!(text.starts_with(Language::De.t("print_title_cards")));
+        assert!(text.contains(Language::De.t("print_letter_blank")));
+        assert!(text.contains("☐ Stadt\n☐ Land\n"));
+        assert!(!text.contains(Language::De.t("print_scores")));
+    }
+}
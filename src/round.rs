@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+/// What happened to the category that was shown
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Success,
+    Next,
+}
+
+/// A Success/Next action and the second of the round it happened in
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RoundEvent {
+    pub second: u32,
+    pub category: String,
+    pub action: Action,
+}
+
+/// UI-independent state of one round: the drawn card, the categories that are not yet
+/// solved, the index of the category that is currently shown and the timeline of all actions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Round {
+    complete_card: Vec<String>,
+    reduced_card: Vec<String>,
+    current_index: usize,
+    events: Vec<RoundEvent>,
+}
+
+impl Round {
+    /// Start a round with the given card, showing its first category.
+    pub fn new(card: Vec<String>) -> Round {
+        Round { complete_card: card.clone(), reduced_card: card, current_index: 0, events: Vec::new() }
+    }
+
+    /// All categories of the card, solved or not.
+    pub fn complete_card(&self) -> &Vec<String> {
+        &self.complete_card
+    }
+
+    /// Categories that were not solved yet.
+    pub fn reduced_card(&self) -> &Vec<String> {
+        &self.reduced_card
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current_index
+    }
+
+    /// The category that is currently played, `None` if every category was solved.
+    pub fn current_category(&self) -> Option<&String> {
+        self.reduced_card.get(self.current_index)
+    }
+
+    /// All Success/Next actions of the round in the order they happened.
+    pub fn events(&self) -> &Vec<RoundEvent> {
+        &self.events
+    }
+
+    /// Seconds `category` was shown until it was solved, `None` if it wasn't solved.
+    /// Each action ends the period in which its category was shown, which started with the action before.
+    pub fn solve_time(&self, category: &str) -> Option<u32> {
+        let mut shown = 0;
+        let mut since = 0;
+        for event in &self.events {
+            if event.category == category {
+                shown += event.second - since;
+                if event.action == Action::Success {
+                    return Some(shown);
+                }
+            }
+            since = event.second;
+        }
+        None
+    }
+
+    /// Whether every category of the card was solved.
+    pub fn is_finished(&self) -> bool {
+        self.reduced_card.is_empty()
+    }
+
+    /// The current category was solved in `second` of the round: remove it from the card.
+    /// Since an element was removed, the index stays the same or goes back by one
+    /// if the last element was removed.
+    pub fn success(&mut self, second: u32) {
+        if self.is_finished() {
+            return;
+        }
+        let i = self.current_index;
+        let category = self.reduced_card.remove(i);
+        self.events.push(RoundEvent { second, category, action: Action::Success });
+        self.current_index = if i == 0 || i < self.reduced_card.len() { i } else { i - 1 };
+    }
+
+    /// The current category was skipped in `second` of the round: show the next one.
+    /// Since no element was removed, either go back to 0 (if last element) or increase the index by 1.
+    pub fn next(&mut self, second: u32) {
+        if self.is_finished() {
+            return;
+        }
+        let category = self.reduced_card[self.current_index].clone();
+        self.events.push(RoundEvent { second, category, action: Action::Next });
+        self.current_index = if self.current_index == self.reduced_card.len() - 1 { 0 } else { self.current_index + 1 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card() -> Vec<String> {
+        ["A", "B", "C"].iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn success_on_the_last_index_goes_back_one() {
+        let mut round = Round::new(card());
+        round.next(1);
+        round.next(2);
+        assert_eq!(round.current_category().unwrap(), "C");
+        round.success(3);
+        assert_eq!(round.reduced_card(), &vec!["A".to_string(), "B".to_string()]);
+        assert_eq!(round.current_index(), 1);
+        assert_eq!(round.current_category().unwrap(), "B");
+    }
+
+    #[test]
+    fn success_of_the_last_category_finishes_the_round() {
+        let mut round = Round::new(vec!["A".to_string()]);
+        round.success(4);
+        assert!(round.is_finished());
+        assert_eq!(round.current_category(), None);
+        // further actions are ignored
+        round.success(5);
+        round.next(6);
+        assert_eq!(round.events().len(), 1);
+    }
+
+    #[test]
+    fn next_wraps_around() {
+        let mut round = Round::new(card());
+        round.next(1);
+        round.next(2);
+        round.next(3);
+        assert_eq!(round.current_index(), 0);
+        assert_eq!(round.current_category().unwrap(), "A");
+        assert_eq!(round.events().iter().map(|e| e.action).collect::<Vec<_>>(), vec![Action::Next; 3]);
+    }
+
+    #[test]
+    fn solve_time_adds_up_every_time_the_category_was_shown() {
+        let mut round = Round::new(card());
+        round.next(4); // A shown 0..4
+        round.next(6); // B shown 4..6
+        round.next(7); // C shown 6..7
+        round.success(10); // A shown 7..10
+        assert_eq!(round.solve_time("A"), Some(7));
+        round.success(15); // B shown 10..15
+        assert_eq!(round.solve_time("B"), Some(7));
+        assert_eq!(round.solve_time("C"), None);
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::game::Game;
+
+fn directory(data_dir: &Path) -> PathBuf {
+    data_dir.join("rooms")
+}
+
+fn path(data_dir: &Path, code: &str) -> PathBuf {
+    directory(data_dir).join(format!("{code}.json"))
+}
+
+/// Save the active game of room `code` to `data_dir`, so it survives a restart
+pub fn save(data_dir: &Path, code: &str, game: &Game) {
+    let result = fs::create_dir_all(directory(data_dir))
+        .and_then(|_| fs::write(path(data_dir, code), serde_json::to_string(game).unwrap()));
+    if let Err(e) = result {
+        eprintln!("Couldn't save the active game of room {code}: {}", e);
+    }
+}
+
+/// Delete the snapshot of room `code`, once the room is closed
+pub fn delete(data_dir: &Path, code: &str) {
+    if let Err(e) = fs::remove_file(path(data_dir, code)) {
+        eprintln!("Couldn't delete the game of room {code}: {}", e);
+    }
+}
+
+/// Remember room `code` as the room of devices that didn't join one
+pub fn save_default(data_dir: &Path, code: &str) {
+    let result = fs::create_dir_all(directory(data_dir))
+        .and_then(|_| fs::write(directory(data_dir).join("default"), code));
+    if let Err(e) = result {
+        eprintln!("Couldn't save the default room {code}: {}", e);
+    }
+}
+
+/// Code of the default room before the app stopped, see `save_default`
+pub fn load_default(data_dir: &Path) -> Option<String> {
+    fs::read_to_string(directory(data_dir).join("default")).ok()
+        .map(|code| code.trim().to_string())
+}
+
+/// Load the games of all rooms that were open when the app stopped, with their room codes
+/// and how long ago they were saved
+pub fn load_all(data_dir: &Path) -> Vec<(String, Game, Duration)> {
+    let Ok(entries) = fs::read_dir(directory(data_dir)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let code = name.strip_suffix(".json")?.to_string();
+            let content = fs::read_to_string(entry.path()).ok()?;
+            let saved = entry.metadata().and_then(|m| m.modified()).ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .unwrap_or_default();
+            match serde_json::from_str(&content) {
+                Ok(game) => Some((code, game, saved)),
+                Err(e) => {
+                    eprintln!("Couldn't restore the game of room {code}: {}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::game::FinishedRound;
+use crate::round::Round;
+
+/// Outcomes of one category over all games
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CategoryStatistics {
+    drawn: u32,
+    solved: u32,
+    solve_seconds: u32,
+}
+
+impl CategoryStatistics {
+    /// Share of draws in which the category was solved
+    pub fn success_rate(&self) -> f64 {
+        if self.drawn == 0 { 0.0 } else { self.solved as f64 / self.drawn as f64 }
+    }
+
+    /// Average seconds the category was shown until it was solved
+    pub fn average_solve_time(&self) -> Option<f64> {
+        if self.solved == 0 { None } else { Some(self.solve_seconds as f64 / self.solved as f64) }
+    }
+}
+
+/// One row of the statistics page
+#[derive(Serialize, Debug)]
+pub struct CategoryRow {
+    category: String,
+    drawn: u32,
+    solved: u32,
+    success_rate: u32,
+    average_solve_time: Option<String>,
+}
+
+/// Statistics of all categories of one collection, the hardest and easiest ones first and last.
+#[derive(Serialize, Debug)]
+pub struct CollectionStatistics {
+    name: String,
+    rows: Vec<CategoryRow>,
+    hardest: Option<String>,
+    easiest: Option<String>,
+}
+
+/// Held while the statistics file is read, updated and written again, so rooms that finish
+/// rounds at the same time don't lose each other's updates
+static RECORDING: Mutex<()> = Mutex::new(());
+
+/// Per category outcomes of all played rounds, persisted as JSON in the data directory
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Statistics {
+    categories: BTreeMap<String, CategoryStatistics>,
+}
+
+impl Statistics {
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join("statistics.json")
+    }
+
+    /// Load the statistics from `data_dir`, empty if there are none yet.
+    /// Fails if there is a file that can't be read.
+    fn read(data_dir: &Path) -> io::Result<Statistics> {
+        match fs::read_to_string(Self::path(data_dir)) {
+            Ok(content) => serde_json::from_str(&content).map_err(io::Error::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Statistics::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Load the statistics from `data_dir`, empty if there are none yet or they can't be read.
+    pub fn load(data_dir: &Path) -> Statistics {
+        Self::read(data_dir).unwrap_or_else(|e| {
+            eprintln!("Couldn't read statistics: {}", e);
+            Statistics::default()
+        })
+    }
+
+    /// Save the statistics to `data_dir`. They are written to a temporary file first, which
+    /// then replaces the old one, so an interrupted write can't leave a broken file.
+    pub fn save(&self, data_dir: &Path) {
+        let path = Self::path(data_dir);
+        let temporary = path.with_extension("json.tmp");
+        let result = fs::create_dir_all(data_dir)
+            .and_then(|_| fs::write(&temporary, serde_json::to_string_pretty(self).unwrap()))
+            .and_then(|_| fs::rename(&temporary, &path));
+        if let Err(e) = result {
+            eprintln!("Couldn't write statistics: {}", e);
+        }
+    }
+
+    /// Add the outcome of every category of a finished round. Translated categories are
+    /// recorded as the category of `originals` they were translated from.
+    pub fn record_round(&mut self, round: &Round, originals: &HashMap<String, String>) {
+        for category in round.complete_card() {
+            let original = originals.get(category).unwrap_or(category);
+            let statistics = self.categories.entry(original.clone()).or_default();
+            statistics.drawn += 1;
+            if let Some(seconds) = round.solve_time(category) {
+                statistics.solved += 1;
+                statistics.solve_seconds += seconds;
+            }
+        }
+    }
+
+    /// Add the finished rounds to the statistics stored in `data_dir`, see `record_round`
+    pub fn record_rounds(data_dir: &Path, finished: &[FinishedRound], originals: &HashMap<String, String>) {
+        if finished.is_empty() {
+            return;
+        }
+        // a panic while recording leaves the file as it was, so a poisoned lock is fine
+        let _recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+        // recording into empty statistics would overwrite the ones that couldn't be read
+        let mut statistics = match Statistics::read(data_dir) {
+            Ok(statistics) => statistics,
+            Err(e) => {
+                eprintln!("Couldn't read statistics, the rounds aren't recorded: {}", e);
+                return;
+            }
+        };
+        for f in finished {
+            statistics.record_round(&f.round, originals);
+        }
+        statistics.save(data_dir);
+    }
+
+    /// Statistics of the categories of collection `name`, sorted from hardest to easiest.
+    /// A category is harder the less often it was solved and the longer it took to solve it.
+    /// Categories that were never drawn are listed last.
+    pub fn collection(&self, name: &str, categories: &[String]) -> CollectionStatistics {
+        let mut drawn: Vec<(&String, &CategoryStatistics)> = categories.iter()
+            .filter_map(|c| self.categories.get(c).map(|s| (c, s)))
+            .filter(|(_, s)| s.drawn > 0)
+            .collect();
+        drawn.sort_by(|(_, a), (_, b)| {
+            a.success_rate().total_cmp(&b.success_rate())
+                .then(b.average_solve_time().unwrap_or(f64::MAX).total_cmp(&a.average_solve_time().unwrap_or(f64::MAX)))
+        });
+        let hardest = drawn.first().map(|(c, _)| c.to_string());
+        let easiest = if drawn.len() > 1 { drawn.last().map(|(c, _)| c.to_string()) } else { None };
+
+        let mut rows: Vec<CategoryRow> = drawn.iter()
+            .map(|(c, s)| CategoryRow
+                { category: c.to_string()
+                , drawn: s.drawn
+                , solved: s.solved
+                , success_rate: (s.success_rate() * 100.0).round() as u32
+                , average_solve_time: s.average_solve_time().map(|t| format!("{t:.1}"))
+                })
+            .collect();
+        rows.extend(categories.iter()
+            .filter(|c| !drawn.iter().any(|(d, _)| d == c))
+            .map(|c| CategoryRow
+                { category: c.clone()
+                , drawn: 0
+                , solved: 0
+                , success_rate: 0
+                , average_solve_time: None
+                }));
+
+        CollectionStatistics { name: name.to_string(), rows, hardest, easiest }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_translated_categories_as_written() {
+        let mut round = Round::new(vec!["Fluss".to_string(), "City".to_string()]);
+        round.success(4);
+        round.next(6);
+        let originals = HashMap::from([("City".to_string(), "Stadt".to_string())]);
+        let mut statistics = Statistics::default();
+        statistics.record_round(&round, &originals);
+
+        let collection = statistics.collection("default", &["Stadt".to_string(), "Fluss".to_string(), "Land".to_string()]);
+        let drawn: Vec<(&str, u32, u32)> = collection.rows.iter()
+            .map(|row| (row.category.as_str(), row.drawn, row.solved))
+            .collect();
+        assert_eq!(drawn, vec![("Stadt", 1, 0), ("Fluss", 1, 1), ("Land", 0, 0)]);
+    }
+
+    #[test]
+    fn keeps_statistics_that_can_not_be_read() {
+        let data_dir = std::env::temp_dir().join(format!("slv-statistics-{}", std::process::id()));
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::write(Statistics::path(&data_dir), "{\"categories\": {").unwrap();
+        let mut round = Round::new(vec!["Stadt".to_string()]);
+        round.success(3);
+        let finished = FinishedRound { player: None, letter: 'B', timeout: 60, round };
+
+        Statistics::record_rounds(&data_dir, &[finished], &HashMap::new());
+        assert_eq!(fs::read_to_string(Statistics::path(&data_dir)).unwrap(), "{\"categories\": {");
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
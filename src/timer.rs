@@ -1,6 +1,13 @@
-use std::{thread, time::Duration};
-use std::io::{Write, stdout};
-use crossterm::{QueueableCommand, cursor, terminal, ExecutableCommand};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::io::{Stdout, Write, stdout};
+use crossterm::{QueueableCommand, cursor, event, terminal, ExecutableCommand};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Stylize};
+
+use crate::config::Rules;
+use crate::game::{Game, TimeLevel};
+use crate::i18n::Language;
 
 // Timeout struct for input validation
 pub struct TimeoutFromString {
@@ -8,42 +15,131 @@ pub struct TimeoutFromString {
     value: u32,
 }
 impl TimeoutFromString {
-    pub fn new(input: String) -> TimeoutFromString {
+    /// Timeout typed in as `input`, the default timeout of `rules` if it is empty.
+    /// Fails with the message to show if it isn't a number in the range of `rules`.
+    pub fn new(input: String, rules: &Rules, lang: Language) -> Result<TimeoutFromString, String> {
         let value: u32 = match input.trim().parse() {
             Ok(value) => {
-                if value < 1 || value > 999 {
-                    panic!("Please provide a positive timeout lower than 999s!");
+                if !(rules.timeout_min..=rules.timeout_max).contains(&value) {
+                    return Err(lang.format("timeout_range", &[("min", &rules.timeout_min), ("max", &rules.timeout_max)]));
                 }
                 value
             },
+            Err(_) if input.trim().is_empty() && rules.default_timeout.is_some() => {
+                rules.default_timeout.unwrap()
+            },
             Err(_) => {
-                panic!("Please type a number!");
+                return Err(lang.t("timeout_number").to_string());
             }
         };
-        TimeoutFromString { value }
+        Ok(TimeoutFromString { value })
     }
     pub fn value(&self) -> u32 {
         self.value
     }
 }
 
-pub fn start_timer(timeout_from_string: TimeoutFromString) {
-    println!("Timer:");
+/// Ring the terminal bell `times` times, with a short pause so the bells don't merge into one
+fn bell(stdout: &mut Stdout, times: u32) {
+    for i in 0..times {
+        if i > 0 {
+            thread::sleep(Duration::from_millis(150));
+        }
+        stdout.write_all(b"\x07").unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+/// Width of the progress bar in characters
+const BAR_WIDTH: usize = 20;
+
+/// Progress bar of the time left in the round of `game`, green while there is plenty of time,
+/// yellow when it runs low and red at the end
+fn progress_bar(game: &Game) -> String {
+    let filled = (game.remaining_fraction().unwrap_or(0.0) * BAR_WIDTH as f64).ceil() as usize;
+    let color = match game.time_level() {
+        Some(TimeLevel::Plenty) => Color::Green,
+        Some(TimeLevel::Low) => Color::Yellow,
+        Some(TimeLevel::Critical) | None => Color::Red,
+    };
+    let bar = "█".repeat(filled.min(BAR_WIDTH)) + &"░".repeat(BAR_WIDTH - filled.min(BAR_WIDTH));
+    format!("{}", bar.with(color))
+}
+
+/// Run the timer of the started round of `game` and let the player cycle through the categories
+/// with single key presses: `s` marks the current category as solved, `n` skips to the next one.
+/// Every passed second is handed to the game, which finishes the round once the time is over
+/// or every category was solved. `Ctrl-C` stops the round early.
+/// A progress bar shows the time left and changes its color as the time runs low.
+/// With `sounds` the terminal bell ticks in the last 5 seconds, rings three times when the time
+/// is over and once for every success.
+pub fn start_timer(game: &mut Game, sounds: bool, lang: Language) {
+    println!("{}", lang.t("timer_help"));
     let mut stdout = stdout();
-    let timeout = timeout_from_string.value();
+    let start = Instant::now();
+    let mut passed = 0;
 
+    terminal::enable_raw_mode().unwrap();
     stdout.execute(cursor::Hide).unwrap();
-    for s in 0..(timeout+1) {
-        stdout.queue(cursor::SavePosition).unwrap();
-        stdout.write_all(format!("{:>3} /{timeout:>3} seconds.", s).as_bytes()).unwrap();
-        if s != timeout {
-            stdout.queue(cursor::RestorePosition).unwrap();
-            stdout.flush().unwrap();
-            thread::sleep(Duration::from_secs(1));
-            stdout.queue(cursor::RestorePosition).unwrap();
-            stdout.queue(terminal::Clear(terminal::ClearType::FromCursorDown)).unwrap();
+    while game.is_running() {
+        while passed < start.elapsed().as_secs() {
+            game.tick();
+            passed += 1;
+            match game.remaining() {
+                Some(1..=5) if sounds && game.is_running() => bell(&mut stdout, 1),
+                Some(0) if sounds => bell(&mut stdout, 3),
+                _ => (),
+            }
+        }
+        let category = game.round().and_then(|r| r.current_category()).cloned().unwrap_or_default();
+        stdout.queue(cursor::MoveToColumn(0)).unwrap();
+        stdout.queue(terminal::Clear(terminal::ClearType::CurrentLine)).unwrap();
+        let remaining = game.remaining().unwrap_or(0);
+        let status = lang.format("timer_status",
+            &[("bar", &progress_bar(game)), ("remaining", &format!("{remaining:>3}")), ("category", &category)]);
+        stdout.write_all(status.as_bytes()).unwrap();
+        stdout.flush().unwrap();
+        if event::poll(Duration::from_millis(100)).unwrap() {
+            if let Event::Key(key) = event::read().unwrap() {
+                if key.kind != KeyEventKind::Press {
+                    continue
+                }
+                // the round is running, so these transitions can't fail
+                match key.code {
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => game.finish(),
+                    KeyCode::Char('s') => {
+                        game.success().unwrap();
+                        if sounds {
+                            bell(&mut stdout, 1);
+                        }
+                    },
+                    KeyCode::Char('n') => game.skip().unwrap(),
+                    _ => (),
+                }
+            }
         }
     }
     stdout.execute(cursor::Show).unwrap();
+    terminal::disable_raw_mode().unwrap();
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeout(input: &str, rules: &Rules) -> Result<u32, String> {
+        TimeoutFromString::new(input.to_string(), rules, Language::En).map(|timeout| timeout.value())
+    }
+
+    #[test]
+    fn rejects_typos_instead_of_panicking() {
+        let rules = Rules { timeout_min: 10, timeout_max: 100, ..Rules::default() };
+        assert_eq!(timeout(" 60\n", &rules), Ok(60));
+        assert_eq!(timeout("6o\n", &rules), Err(Language::En.t("timeout_number").to_string()));
+        assert!(timeout("5\n", &rules).is_err());
+        assert!(timeout("\n", &rules).is_err());
+        let rules = Rules { default_timeout: Some(45), ..rules };
+        assert_eq!(timeout("\n", &rules), Ok(45));
+    }
+}
@@ -1,21 +1,48 @@
 use axum::{
     extract::State,
     extract::Form,
-    http::{StatusCode, Uri, header},
-    response::{Html, IntoResponse},
-    routing::get,
+    extract::Path as UrlPath,
+    async_trait,
+    extract::{DefaultBodyLimit, FromRequestParts, Host, Multipart, Query},
+    extract::multipart::MultipartError,
+    http::request::Parts,
+    http::{HeaderMap, StatusCode, Uri, header},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
     Router,
 };
-use minijinja::{context, Environment};
+use minijinja::{context, HtmlEscape};
 use rust_embed_for_web::{EmbedableFile, RustEmbed};
 use serde::Deserialize;
+use rand::rngs::StdRng;
+use std::convert::Infallible;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::thread;
 use std::time::Duration;
+use tokio::signal;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
+mod api;
+mod rooms;
+pub(crate) mod templates;
+
+use crate::background::{self, BackgroundError};
+use crate::bundle::{Bundle, BundleError, Conflict};
 use crate::cards;
 use crate::dice;
+use crate::game::{Game, GameError, Phase};
+use crate::history::GameRecord;
+use crate::i18n::Language;
+use crate::print::{Format, Sheet};
+use crate::snapshot;
+use crate::statistics::Statistics;
+use crate::{record_finished, Settings};
+use rooms::{join_cookies, Room, Rooms};
+use templates::Templates;
 
 #[derive(RustEmbed)]
 #[folder = "src/assets/"]
@@ -23,388 +50,996 @@ struct Asset;
 
 /// Game state that is fixed per session
 struct GameState {
-    categories: Mutex<Vec<String>>,
-    round_state: Mutex<RoundState>,
-    environment: Environment<'static>,
-}
-
-/// RoundState that is cleaned before each new round
-struct RoundState {
-    timeout: Option<u32>,
-    letter: Option<char>,
-    reduced_card: Option<Vec<String>>,
-    complete_card: Option<Vec<String>>,
-    category: Option<String>,
-    current_index: Option<usize>,
+    rooms: Rooms,
+    rng: Mutex<StdRng>,
+    settings: Settings,
+    templates: Templates,
 }
 
-impl RoundState {
-    /// Create an empty RoundState
-    pub fn empty() -> RoundState {
-        Self::new(None, None, None, None, None, None)
+impl GameState {
+    /// Record the finished rounds and snapshot the game of room `code` after every transition,
+    /// so it can be restored after a restart.
+    fn persist(&self, code: &str, game: &mut Game) {
+        record_finished(&self.settings.data_dir, game);
+        snapshot::save(&self.settings.data_dir, code, game);
     }
-    /// Create a new RoundState with the given fields
-    pub fn new
-        ( timeout: Option<u32>
-        , letter: Option<char>
-        , reduced_card: Option<Vec<String>>
-        , complete_card: Option<Vec<String>>
-        , category: Option<String>
-        , current_index: Option<usize>
-    ) -> RoundState {
-        RoundState { timeout, letter, reduced_card, complete_card, category, current_index }
-    }
-    /// Update a RoundState with the given value
-    pub fn update_timeout(&mut self, timeout: Option<u32>) {
-        self.timeout = timeout;
+
+    /// Render template `name` with `context`, the error page with status 500 if that fails
+    fn render(&self, lang: Language, name: &str, context: minijinja::Value) -> Result<Html<String>, (StatusCode, Html<String>)> {
+        self.templates.render(name, context)
+            .map(Html)
+            .map_err(|_| error_page(self, lang, StatusCode::INTERNAL_SERVER_ERROR, lang.t("error_render").to_string()))
     }
-    pub fn update_letter(&mut self, letter: Option<char>) {
-        self.letter = letter;
+
+    /// Close the rooms abandoned for `rooms::ABANDONED_AFTER` and delete their snapshots
+    fn close_abandoned_rooms(&self) {
+        for code in self.rooms.close_unused(rooms::ABANDONED_AFTER) {
+            snapshot::delete(&self.settings.data_dir, &code);
+            println!("{}", self.settings.language().format("serve_closed_room", &[("code", &code)]));
+        }
     }
-    pub fn update_reduced_card(&mut self, reduced_card: Option<Vec<String>>) {
-        self.reduced_card = reduced_card;
+
+    /// Add the categories of collection `name` to the game, in the language of the game
+    fn add_collection(&self, game: &mut Game, name: &str) -> Result<(), GameError> {
+        if !cards::is_collection(name) {
+            return Err(GameError::UnknownCollection(name.to_string()));
+        }
+        game.add_collection(name, cards::load_categories(name, game.language()))
     }
-    pub fn update_complete_card(&mut self, complete_card: Option<Vec<String>>) {
-        self.complete_card = complete_card;
+
+    /// Play the categories of all collections of the game in `language`
+    fn set_language(&self, game: &mut Game, language: Option<Language>) -> Result<(), GameError> {
+        let categories = game.collections().iter()
+            .flat_map(|name| cards::load_categories(name, language))
+            .collect();
+        game.set_language(language, categories)
     }
-    pub fn update_category(&mut self, category: Option<String>) {
-        self.category = category;
+
+    /// Roll the dice of the house rules, matching the language of the game
+    fn roll(&self, game: &mut Game) -> Result<char, GameError> {
+        let dice = self.settings.config.rules.dice(game.language());
+        let letter = dice::roll_dice(&dice, &mut *self.rng.lock().unwrap()).chars().next().unwrap();
+        game.roll(letter)
     }
-    pub fn update_current_index(&mut self, current_index: Option<usize>) {
-        self.current_index = current_index;
+
+    /// Draw a card and start the round, if `timeout` is within the bounds of the house rules
+    fn start(&self, game: &mut Game, timeout: u32) -> Result<(), GameError> {
+        let rules = &self.settings.config.rules;
+        if !(rules.timeout_min..=rules.timeout_max).contains(&timeout) {
+            return Err(GameError::InvalidTimeout { min: rules.timeout_min, max: rules.timeout_max });
+        }
+        let card = cards::draw_card(game.categories(), rules.card_size, &mut *self.rng.lock().unwrap());
+        game.start(timeout, card)
     }
 }
 
 #[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-/// Input to set up a game session, used to set `GameState`
+/// Input to set up a game session, used to set up the `Game`
 struct GameInput {
     collection_name: String,
 }
 
 #[derive(Deserialize, Debug)]
-#[allow(dead_code)]
-/// New input for each round, used to set `RoundState`
+/// Language the categories are played in, empty to play them as written
+struct LanguageInput {
+    language: String,
+}
+
+#[derive(Deserialize, Debug)]
+/// Comma separated names of the players, who take turns in the given order
+struct PlayersInput {
+    players: String,
+}
+
+#[derive(Deserialize, Debug)]
+/// Token of the round step a form was rendered for, see `Game::round_token`
+struct TokenInput {
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+/// New input for each round, used to drive the `Game`
 struct RoundInput {
+    token: String,
     timeout: Option<u32>,
     success: Option<bool>,
 }
 
-/// Serves the game app and spawns a timeout-checking thread
-pub async fn serve() {
-    let mut env = Environment::new();
-    env.add_template("layout", include_str!("./templates/layout.jinja")).unwrap();
-    env.add_template("home", include_str!("./templates/home.jinja")).unwrap();
-    env.add_template("categories", include_str!("./templates/categories.jinja")).unwrap();
-    env.add_template("start", include_str!("./templates/start.jinja")).unwrap();
-    env.add_template("round", include_str!("./templates/round.jinja")).unwrap();
-    env.add_template("timer", include_str!("./templates/timer.jinja")).unwrap();
-    env.add_template("result", include_str!("./templates/result.jinja")).unwrap();
+/// Language of the request: the one of the config or command line, otherwise the one
+/// preferred by the browser
+struct Lang(Language);
 
-    // Prepare `GameState` with empty state and the environment
+#[async_trait]
+impl FromRequestParts<Arc<GameState>> for Lang {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<GameState>) -> Result<Self, Self::Rejection> {
+        let preferred = || parts.headers.get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Language::from_accept_language);
+        Ok(Lang(state.settings.config.ui.language.or_else(preferred).unwrap_or_default()))
+    }
+}
+
+/// Serves the game app on `address` and spawns a timeout-checking task.
+/// Stops gracefully on Ctrl-C or SIGTERM: open requests are finished, the task is stopped
+/// and the active games are saved.
+pub async fn serve(settings: Settings, address: SocketAddr) -> io::Result<()> {
+    // Prepare `GameState` with the rooms that were open before the last restart
+    // (or one with an empty game) and the templates
+    let lang = settings.language();
+    let games = snapshot::load_all(&settings.data_dir);
+    for (code, game, _) in &games {
+        let args: [(&str, &dyn std::fmt::Display); 2] = [("started", &game.started()), ("code", code)];
+        println!("{}", lang.format("serve_restored", &args));
+    }
+    let rooms = Rooms::new(games, snapshot::load_default(&settings.data_dir), || new_game(&settings));
+    snapshot::save_default(&settings.data_dir, rooms.default_code());
     let game_state = Arc::new(GameState
-        { categories: Mutex::new(Vec::new())
-        , round_state: Mutex::new(RoundState::empty())
-        , environment: env
+        { rooms
+        , rng: Mutex::new(settings.rng())
+        , templates: Templates::new(settings.config.ui.templates.clone(), settings.config.server.dev, settings.config.ui.theme)
+        , settings
         });
 
-    // Spawns a timeout thread that counts down the timeout of each round if set
-    let handle = spawn_timeout_thread(Arc::clone(&game_state));
+    game_state.close_abandoned_rooms();
+
+    // Bind first, so the timeout task isn't left running if the address is taken
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    let address = listener.local_addr()?;
+
+    // Spawns a timeout task that counts down the timeout of each round if set
+    let shutdown = CancellationToken::new();
+    let handle = spawn_timeout_task(Arc::clone(&game_state), shutdown.clone());
 
     let app = Router::new()
         .route("/", get(handler_home))
-        .route("/slv.png", get(get_background_png))
-        .route("/start", get(handler_start_game).post(post_start_game))
+        .route("/assets/*path", get(get_asset))
+        .route("/sw.js", get(get_service_worker))
+        .route("/background", get(get_background).post(post_background)
+            // room for the multipart framing around the largest image
+            .layer(DefaultBodyLimit::max(background::MAX_SIZE + 64 * 1024)))
+        .route("/background/delete", post(post_delete_background))
+        .route("/start", get(handler_start_game))
+        .route("/players", post(post_players))
+        .route("/language", post(post_language))
         .route("/categories", get(handler_categories).post(post_categories))
+        .route("/categories/delete", post(post_delete_categories))
+        .route("/collections/:name/export", get(handler_export_collection))
+        .route("/collections/import", post(post_import_collection))
         .route("/round", get(handler_start_round).post(post_start_round))
+        .route("/round/delete", post(post_delete_round))
         .route("/timer", get(handler_start_timer).post(post_start_timer))
-        .route("/result", get(handler_result))
+        .route("/result", get(handler_result).post(post_result))
+        .route("/display", get(handler_display))
+        .route("/rooms", post(post_rooms))
+        .route("/join", get(handler_join_code))
+        .route("/join/:code", get(handler_join).post(post_join))
+        .route("/leave", post(post_leave))
+        .route("/statistics", get(handler_statistics))
+        .route("/history", get(handler_history))
+        .route("/history/:id", get(handler_history_game))
+        .route("/print/game/:id", get(handler_print_game))
+        .route("/print/cards", get(handler_print_cards))
+        .nest("/api/v1", api::router())
         .route("/*uri", get(not_found))
-        .with_state(game_state);
+        .with_state(Arc::clone(&game_state));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3333").await.unwrap();
-    println!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
-    handle.join().unwrap();
+    println!("{}", lang.format("serve_listening", &[("address", &address)]));
+    for url in urls(address) {
+        println!("- {url}");
+    }
+    let served = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal(shutdown.clone(), lang))
+        .await;
+
+    // also stop the timeout task if serving failed
+    shutdown.cancel();
+    if let Err(e) = handle.await {
+        eprintln!("Couldn't stop the timeout task: {}", e);
+    }
+    for (code, game) in game_state.rooms.all() {
+        // a room whose handler panicked keeps the snapshot of its last transition
+        match game.lock() {
+            Ok(mut game) => game_state.persist(&code, &mut game),
+            Err(_) => eprintln!("Couldn't save the game of room {code}, it is broken"),
+        }
+    }
+    println!("{}", lang.t("serve_saved"));
+    served
 }
 
-/// Spawn a thread that loops indefinitely.
-/// Each loop puts the thread to sleep for 1 second and reduces the
-/// timeout variable by 1 if set and not 0 already.
-fn spawn_timeout_thread(state: Arc<GameState>) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
+/// Resolves on Ctrl-C or SIGTERM and cancels `shutdown`, so everything else stops too.
+/// Tells on the console in `lang` that the server is shutting down.
+async fn shutdown_signal(shutdown: CancellationToken, lang: Language) {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("failed to listen for Ctrl-C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+        _ = shutdown.cancelled() => {},
+    }
+    println!("{}", lang.t("serve_shutting_down"));
+    shutdown.cancel();
+}
+
+/// Create a game with the default collections of the house rules, in the configured language
+fn new_game(settings: &Settings) -> Game {
+    Game::with_collections(settings.config.ui.language, &settings.config.rules.default_collections)
+}
+
+/// URLs under which the app listening on `address` can be opened.
+/// When listening on all interfaces this includes the address in the local network,
+/// so other devices at the table can join.
+fn urls(address: SocketAddr) -> Vec<String> {
+    let port = address.port();
+    if address.ip().is_unspecified() {
+        let mut urls = vec![format!("http://localhost:{port}")];
+        if let Some(ip) = lan_address() {
+            urls.push(format!("http://{}", SocketAddr::new(ip, port)));
+        }
+        urls
+    } else if address.ip().is_loopback() {
+        vec![format!("http://localhost:{port}")]
+    } else {
+        vec![format!("http://{address}")]
+    }
+}
+
+/// Address of this machine in the local network. Connecting a UDP socket sends no packets,
+/// it only makes the OS choose the interface that would be used to reach the outside.
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 168, 0, 1), 9)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    if ip.is_unspecified() || ip.is_loopback() { None } else { Some(ip) }
+}
+
+/// Spawn a task that loops until `shutdown` is cancelled.
+/// Each loop waits for 1 second and lets one second
+/// pass in the game of every room, which finishes a running round once its time is over.
+/// Once an hour it closes the abandoned rooms.
+fn spawn_timeout_task(state: Arc<GameState>, shutdown: CancellationToken) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        let mut cleanup = tokio::time::interval(Duration::from_secs(60 * 60));
+        // the first ticks complete immediately
+        interval.tick().await;
+        cleanup.tick().await;
         loop {
-            thread::sleep(Duration::from_secs(1));
-            let mut round_state = state.round_state.lock().unwrap();
-            if round_state.timeout.is_some() {
-                let t = round_state.timeout.unwrap();
-                round_state.update_timeout(Some(if t > 0 { t - 1 } else { t }));
-            };
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = cleanup.tick() => state.close_abandoned_rooms(),
+                _ = interval.tick() => {
+                    for (code, game) in state.rooms.all() {
+                        // skip rooms whose handler panicked, so the others keep counting down
+                        let Ok(mut game) = game.lock() else {
+                            continue;
+                        };
+                        if game.is_running() {
+                            game.tick();
+                            // the remaining time is saved with every other step and on shutdown,
+                            // so the file only needs writing once the round is over
+                            if !game.is_running() {
+                                state.persist(&code, &mut game);
+                            }
+                        }
+                    }
+                },
+            }
         };
     })
 }
 
-// Fallback route for anything that doesn't match
-async fn not_found(State(state): State<Arc<GameState>>, uri: Uri) -> impl IntoResponse {
-    // Re-use "home" template since it has the same format
-    let template = state.environment.get_template("home").unwrap();
+/// Whether a submitted form was rendered for the current step of the round.
+/// Repeated submissions of an older form are ignored and only redirect to the current page.
+fn is_current(game: &Game, token: &str) -> bool {
+    game.round_token() == token
+}
 
-    let path = uri.path().trim_start_matches('/');
+/// Status code for a transition that the `Game` rejected
+fn error_status(error: &GameError) -> StatusCode {
+    match error {
+        GameError::UnknownCollection(_) => StatusCode::NOT_FOUND,
+        GameError::InvalidTimeout { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        GameError::NotYourTurn(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::CONFLICT,
+    }
+}
 
-    let rendered = template
-        .render(context! {
-            title => "404",
-            welcome_text => format!("Not Found: /{path}"),
+/// Response for a transition that the `Game` rejected.
+fn game_error(state: &GameState, lang: Language, error: GameError) -> (StatusCode, Html<String>) {
+    error_page(state, lang, error_status(&error), lang.error(&error))
+}
+
+/// Response for a request that can't be handled, displaying the reason.
+fn error_page(state: &GameState, lang: Language, status: StatusCode, message: String) -> (StatusCode, Html<String>) {
+    message_page(state, lang, status, lang.t("title_error"), message)
+}
+
+/// Page with `title` and `message`, re-uses the "home" template
+fn message_page(state: &GameState, lang: Language, status: StatusCode, title: &str, message: String) -> (StatusCode, Html<String>) {
+    let rendered = state.templates.render("home", context! {
+            lang => lang,
+            title => title,
+            welcome_text => &message,
         })
-        .unwrap();
+        // the page can't be rendered, at least tell the reason
+        .unwrap_or_else(|_| HtmlEscape(&message).to_string());
 
-    (StatusCode::NOT_FOUND, Html(rendered)).into_response()
+    (status, Html(rendered))
 }
 
-/// Handler for "Home". Does nothing in particular.
-async fn handler_home(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("home").unwrap();
+// Fallback route for anything that doesn't match
+async fn not_found(State(state): State<Arc<GameState>>, Lang(lang): Lang, uri: Uri) -> impl IntoResponse {
+    let path = uri.path().trim_start_matches('/');
 
-    let rendered = template
-        .render(context! {
-            title => "Stadt Land Vollpfosten - digital helper",
-            welcome_text => "Press 'Start New Game' to start a game!",
-        })
-        .unwrap();
+    message_page(&state, lang, StatusCode::NOT_FOUND, lang.t("title_not_found"), lang.format("not_found", &[("path", &path)]))
+}
 
-    Ok(Html(rendered))
+/// Handler for "Home". Offers to continue the last game if there is one and shows how other
+/// devices join the room.
+async fn handler_home(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Host(host): Host) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let game = room.game.lock().unwrap();
+    let continue_url = match game.phase() {
+        _ if game.categories().is_empty() => None,
+        Phase::Running { .. } | Phase::Finished { .. } => Some("/timer"),
+        Phase::Setup | Phase::Rolled { .. } => Some("/round"),
+    };
+
+    let rendered = state.render(lang, "home", context! {
+            lang => lang,
+            title => lang.t("app_title"),
+            welcome_text => lang.t("home_welcome"),
+            continue_url => continue_url,
+            started => game.started(),
+            room => room.code,
+            join_url => format!("http://{host}/join/{}", room.code),
+            me => room.player,
+            joined => room.code != state.rooms.default_code(),
+        })?;
+
+    Ok(rendered)
+}
+
+/// Get handler for the embedded files of `src/assets`: styles, scripts, images and sounds.
+async fn get_asset(UrlPath(path): UrlPath<String>, headers: HeaderMap) -> Response {
+    asset_response(&path, &headers)
+}
+
+/// Get handler for the service worker, which must be served from the root to control every page.
+async fn get_service_worker(headers: HeaderMap) -> Response {
+    asset_response("sw.js", &headers)
+}
+
+/// Get handler for the background image of the game: the uploaded one, otherwise the one of
+/// the config or the built-in image.
+async fn get_background(State(state): State<Arc<GameState>>, room: Room, headers: HeaderMap) -> Response {
+    let id = room.game.lock().unwrap().id().to_string();
+    let path = background::find(&state.settings.data_dir, &id).or_else(|| state.settings.config.ui.background.clone());
+    match path {
+        Some(path) => file_response(&path, &headers),
+        None => asset_response("slv.png", &headers),
+    }
+}
+
+/// Response with the image at `path`, revalidated like the embedded files with an `ETag`
+/// made of its size and modification time
+fn file_response(path: &std::path::Path, headers: &HeaderMap) -> Response {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            eprintln!("Couldn't read background image {}: {}", path.display(), e);
+            return (StatusCode::NOT_FOUND, "404 Not Found").into_response();
+        }
+    };
+    let modified = metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_millis());
+    let etag = format!("\"{}-{modified}\"", metadata.len());
+    let cache = [(header::ETAG, etag.clone()), (header::CACHE_CONTROL, "no-cache".to_string())];
+    let if_none_match = headers.get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    if if_none_match.split(',').any(|tag| tag.trim() == etag) {
+        return (StatusCode::NOT_MODIFIED, cache).into_response();
+    }
+    let content_type = background::content_type(path).unwrap_or("application/octet-stream");
+    match fs::read(path) {
+        Ok(data) => (cache, [(header::CONTENT_TYPE, content_type)], data).into_response(),
+        Err(e) => {
+            eprintln!("Couldn't read background image {}: {}", path.display(), e);
+            (StatusCode::NOT_FOUND, "404 Not Found").into_response()
+        }
+    }
 }
 
-/// Get handler which responds with the background image.
-async fn get_background_png(uri: Uri) -> impl IntoResponse {
-    match Asset::get(uri.path().trim_start_matches('/')) {
-        Some(content) => {
-            ([(header::CONTENT_TYPE, "image/png")], content.data()).into_response()
+/// Post handler for uploading the background image of the game as field `image` of a
+/// multipart form. Redirects to the "Prepare Game" page.
+async fn post_background(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, mut multipart: Multipart) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let too_large = || error_page(&state, lang, StatusCode::PAYLOAD_TOO_LARGE,
+        lang.format("error_background_size", &[("max", &(background::MAX_SIZE / 1024 / 1024))]));
+    let bad_request = || error_page(&state, lang, StatusCode::BAD_REQUEST, lang.t("error_background_missing").to_string());
+    let multipart_error = |e: MultipartError| if e.status() == StatusCode::PAYLOAD_TOO_LARGE { too_large() } else { bad_request() };
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("image") {
+            continue;
         }
-        None => (StatusCode::NOT_FOUND, "404 Not Found").into_response(),
+        let content_type = field.content_type().unwrap_or_default().to_string();
+        let data = field.bytes().await.map_err(multipart_error)?;
+        let id = room.game.lock().unwrap().id().to_string();
+        return match background::save(&state.settings.data_dir, &id, &content_type, &data) {
+            Ok(()) => Ok(Redirect::to("/start")),
+            Err(BackgroundError::UnsupportedType) => Err(error_page(&state, lang, StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                lang.t("error_background_type").to_string())),
+            Err(BackgroundError::TooLarge) => Err(too_large()),
+            Err(BackgroundError::Io(e)) => {
+                eprintln!("Couldn't save background image: {}", e);
+                Err(error_page(&state, lang, StatusCode::INTERNAL_SERVER_ERROR, lang.t("error_background_save").to_string()))
+            },
+        };
     }
+    Err(bad_request())
 }
 
-/// Get handler to prepare a game. Simply displays a page to put in a `collection_name`.
-/// Clears the `RoundState` too.
-async fn handler_start_game(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("start").unwrap();
+/// Post handler to go back to the default background image. Redirects to the
+/// "Prepare Game" page.
+async fn post_delete_background(State(state): State<Arc<GameState>>, room: Room) -> Redirect {
+    let id = room.game.lock().unwrap().id().to_string();
+    background::delete(&state.settings.data_dir, &id);
 
-    let mut round_state = state.round_state.lock().unwrap();
-    *round_state = RoundState::empty();
+    Redirect::to("/start")
+}
 
-    let rendered = template
-        .render(context! {
-            title => "Prepare Game",
-        })
-        .unwrap();
+/// Response with the embedded file at `path`. Its `ETag` lets browsers revalidate their
+/// cached copy on every use, so a new version of the app is picked up right away while
+/// unchanged files cost only a `304 Not Modified`. Precompressed variants are served to
+/// browsers that accept them.
+fn asset_response(path: &str, headers: &HeaderMap) -> Response {
+    let Some(file) = Asset::get(path) else {
+        return (StatusCode::NOT_FOUND, "404 Not Found").into_response();
+    };
+    let etag = file.etag();
+    let etag: &str = etag.as_ref();
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, etag.parse().unwrap());
+    response_headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+    response_headers.insert(header::VARY, "Accept-Encoding".parse().unwrap());
+    if let Some(last_modified) = file.last_modified() {
+        let last_modified: &str = last_modified.as_ref();
+        response_headers.insert(header::LAST_MODIFIED, last_modified.parse().unwrap());
+    }
+
+    let header_value = |name| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    if header_value(header::IF_NONE_MATCH).split(',').any(|tag| tag.trim() == etag) {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
 
-    Ok(Html(rendered))
+    let content_type = match file.mime_type() {
+        Some(mime_type) => AsRef::<str>::as_ref(&mime_type).parse().unwrap(),
+        None => "application/octet-stream".parse().unwrap(),
+    };
+    response_headers.insert(header::CONTENT_TYPE, content_type);
+    let accepted = header_value(header::ACCEPT_ENCODING);
+    let data = match (file.data_br(), file.data_gzip()) {
+        (Some(data), _) if accepted.contains("br") => {
+            response_headers.insert(header::CONTENT_ENCODING, "br".parse().unwrap());
+            data
+        },
+        (_, Some(data)) if accepted.contains("gzip") => {
+            response_headers.insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
+            data
+        },
+        _ => file.data(),
+    };
+    (response_headers, AsRef::<[u8]>::as_ref(&data).to_vec()).into_response()
 }
 
-/// Post handler for preparing a game. Is used when the user wants to delete all saved collections.
-/// Work-around - since "input" does not allow for "delete" method and we don't need the post handler
-/// for anything else. Clears the `RoundState` too.
-async fn post_start_game(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("start").unwrap();
+/// Get handler to prepare a game. Simply displays a page to put in a `collection_name`
+/// and the players.
+async fn handler_start_game(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let game = room.game.lock().unwrap();
+    let rendered = state.render(lang, "start", context! {
+            lang => lang,
+            title => lang.t("title_prepare"),
+            players => game.players(),
+            language => game.language(),
+            collections => cards::collections(),
+            custom_background => background::find(&state.settings.data_dir, game.id()).is_some(),
+        })?;
 
-    let mut categories = state.categories.lock().unwrap();
-    *categories = Vec::new();
-    let mut round_state = state.round_state.lock().unwrap();
-    *round_state = RoundState::empty();
+    Ok(rendered)
+}
 
-    let rendered = template
-        .render(context! {
-            title => "Prepare Game",
-        })
-        .unwrap();
+/// Post handler for setting the players of the game. Redirects to the "Prepare Game" page.
+async fn post_players(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<PlayersInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let players = crate::parse_players(&input.players);
+    let mut game = room.game.lock().unwrap();
+    game.set_players(players).map_err(|e| game_error(&state, lang, e))?;
+    state.persist(&room.code, &mut game);
 
-    Ok(Html(rendered))
+    Ok(Redirect::to("/start"))
 }
 
-/// Get handler for displaying all categories.
-async fn handler_categories(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("categories").unwrap();
+/// Post handler for the language the categories are played in. Redirects to the
+/// "Prepare Game" page.
+async fn post_language(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<LanguageInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    // an empty language plays the categories as written
+    let language = input.language.parse().ok();
+    let mut game = room.game.lock().unwrap();
+    state.set_language(&mut game, language).map_err(|e| game_error(&state, lang, e))?;
+    state.persist(&room.code, &mut game);
+
+    Ok(Redirect::to("/start"))
+}
 
-    let rendered = template
-        .render(context! {
-            title => "Categories",
-            categories => state.categories,
+/// Get handler for displaying all categories.
+async fn handler_categories(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Query(imported): Query<ImportedInput>) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let collections: Vec<_> = cards::collections().into_iter()
+        .map(|name| context! {
+            count => cards::load_categories(&name, None).len(),
+            language => cards::collection_language(&name),
+            name => name,
         })
-        .unwrap();
+        .collect();
+
+    let game = room.game.lock().unwrap();
+    let rendered = state.render(lang, "categories", context! {
+            lang => lang,
+            title => lang.t("title_categories"),
+            categories => game.categories(),
+            token => game.round_token(),
+            collections => collections,
+            imported => imported.name.filter(|name| cards::is_collection(name))
+                .map(|name| context! { name => name, added => imported.added.unwrap_or_default() }),
+        })?;
+
+    Ok(rendered)
+}
+
+#[derive(Deserialize, Debug)]
+/// Collection that was just imported, shown on the categories page
+struct ImportedInput {
+    name: Option<String>,
+    added: Option<usize>,
+}
 
-    Ok(Html(rendered))
+/// Get handler to download collection `name` as bundle file, to import it elsewhere.
+async fn handler_export_collection(UrlPath(name): UrlPath<String>) -> Result<Response, StatusCode> {
+    let bundle = Bundle::export(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let disposition = format!("attachment; filename=\"{}\"", bundle.file_name());
+
+    Ok(([(header::CONTENT_TYPE, "application/json".to_string()), (header::CONTENT_DISPOSITION, disposition)], bundle.to_json()).into_response())
 }
 
-/// Post handler for adding new category collections and displaying all afterwards.
-async fn post_categories(State(state): State<Arc<GameState>>, Form(input): Form<GameInput>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("categories").unwrap();
+/// Post handler to import an uploaded collection bundle, handling a collection of the same
+/// name as chosen in the form. Redirects to the categories page.
+async fn post_import_collection(State(state): State<Arc<GameState>>, Lang(lang): Lang, mut multipart: Multipart) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let invalid = |e: MultipartError| error_page(&state, lang, e.status(), e.body_text());
+    let mut content = None;
+    let mut conflict = Conflict::default();
+    while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+        match field.name() {
+            Some("bundle") => content = Some(field.bytes().await.map_err(invalid)?),
+            Some("conflict") => conflict = field.text().await.map_err(invalid)?.parse().unwrap_or_default(),
+            _ => (),
+        }
+    }
+    let Some(content) = content.filter(|content| !content.is_empty()) else {
+        return Err(error_page(&state, lang, StatusCode::BAD_REQUEST, lang.t("error_bundle_missing").to_string()));
+    };
+
+    let imported = std::str::from_utf8(&content)
+        .map_err(|e| BundleError::Invalid(e.to_string()))
+        .and_then(Bundle::parse)
+        .and_then(|bundle| bundle.import(None, conflict))
+        .map_err(|e| error_page(&state, lang, bundle_error_status(&e), lang.bundle_error(&e)))?;
 
-    let categories = cards::load_categories(&input.collection_name);
-    {
-        let mut cat = state.categories.lock().unwrap();
-        cat.extend(categories.clone());
+    Ok(Redirect::to(&format!("/categories?name={}&added={}", imported.name, imported.added)))
+}
+
+/// Status code for a bundle that can't be imported
+fn bundle_error_status(error: &BundleError) -> StatusCode {
+    match error {
+        BundleError::Invalid(_) | BundleError::UnsupportedVersion(_) | BundleError::InvalidName(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        BundleError::Exists(_) | BundleError::LanguageMismatch { .. } => StatusCode::CONFLICT,
+        BundleError::Io(e) => {
+            eprintln!("Couldn't import collection: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        },
     }
+}
 
-    let rendered = template
-        .render(context! {
-            title => "Categories",
-            categories => state.categories,
-        })
-        .unwrap();
+/// Post handler for adding a category collection. Redirects to the list of all categories.
+async fn post_categories(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<GameInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let mut game = room.game.lock().unwrap();
+    state.add_collection(&mut game, &input.collection_name).map_err(|e| game_error(&state, lang, e))?;
+    state.persist(&room.code, &mut game);
 
-    Ok(Html(rendered))
+    Ok(Redirect::to("/categories"))
 }
 
-/// Get handler to start a new round. Displays the "please roll the dice" button.
-/// Deletes the `RoundState` again, since the user can reach this without setting up new
-/// category collections.
-async fn handler_start_round(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("round").unwrap();
+/// Post handler for deleting all collections and players by starting over with a new game,
+/// not while a round runs. Redirects to the "Prepare Game" page.
+async fn post_delete_categories(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<TokenInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let mut game = room.game.lock().unwrap();
+    if is_current(&game, &input.token) {
+        room.check_turn(&game).map_err(|e| game_error(&state, lang, e))?;
+        game.start_over(new_game(&state.settings)).map_err(|e| game_error(&state, lang, e))?;
+        state.persist(&room.code, &mut game);
+    }
 
-    let mut round_state = state.round_state.lock().unwrap();
-    *round_state = RoundState::empty();
+    Ok(Redirect::to("/start"))
+}
 
-    let rendered = template
-        .render(context! {
-            title => "Start Round",
-            first_round => true,
-        })
-        .unwrap();
+/// Get handler to start a new round. Displays the "please roll the dice" button, or the
+/// timeout input if the dice were already rolled. A running round is continued on the timer page.
+async fn handler_start_round(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room) -> Result<Response, (StatusCode, Html<String>)> {
+    let game = room.game.lock().unwrap();
+    let rendered = match game.phase() {
+        Phase::Running { .. } => return Ok(Redirect::to("/timer").into_response()),
+        Phase::Rolled { letter } => state.render(lang, "round", context! {
+                lang => lang,
+                title => lang.t("title_round"),
+                letter => letter,
+                player => game.current_player(),
+                rules => state.settings.config.rules,
+                token => game.round_token(),
+                phase => game.phase().name(),
+                your_turn => room.check_turn(&game).is_ok(),
+            }),
+        Phase::Setup | Phase::Finished { .. } => state.render(lang, "round", context! {
+                lang => lang,
+                title => lang.t("title_round"),
+                first_round => true,
+                player => game.current_player(),
+                phase => game.phase().name(),
+                token => game.round_token(),
+                your_turn => room.check_turn(&game).is_ok(),
+            }),
+    }?;
 
-    Ok(Html(rendered))
+    Ok(rendered.into_response())
 }
 
-/// Post handler for a new round. Handles the dice roll and displays the additional
-/// input of the current timeout.
-async fn post_start_round(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("round").unwrap();
+/// Post handler for a new round. Rolls the dice and redirects to the input of the timeout.
+async fn post_start_round(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<TokenInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let mut game = room.game.lock().unwrap();
+    if is_current(&game, &input.token) {
+        room.check_turn(&game).map_err(|e| game_error(&state, lang, e))?;
+        state.roll(&mut game).map_err(|e| game_error(&state, lang, e))?;
+        state.persist(&room.code, &mut game);
+    }
 
-    let mut round_state = state.round_state.lock().unwrap();
-    if round_state.letter.is_none() {
-        let letter = dice::roll_dice().chars().next();
-        *round_state = RoundState::empty(); // be sure to empty the state before starting a new round
-        round_state.update_letter(letter);
+    Ok(Redirect::to("/round"))
+}
+
+/// Post handler for dropping the current round without recording it. Redirects to the dice roll.
+async fn post_delete_round(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<TokenInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let mut game = room.game.lock().unwrap();
+    if is_current(&game, &input.token) {
+        room.check_turn(&game).map_err(|e| game_error(&state, lang, e))?;
+        game.reset_round();
+        state.persist(&room.code, &mut game);
     }
 
-    let rendered = template
-        .render(context! {
-            title => "Start Round",
-            letter => round_state.letter,
-        })
-        .unwrap();
+    Ok(Redirect::to("/round"))
+}
 
-    Ok(Html(rendered))
+/// Get handler for a timed round. Simply displays the current round state,
+/// refreshes itself every second while the round is running.
+async fn handler_start_timer(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let game = room.game.lock().unwrap();
+
+    render_timer(&state, lang, &game, &room)
 }
 
-/// Get handler for a timed round. Is called when a refresh happens, never directly in the app.
-/// Simply displays the current round state.
-async fn handler_start_timer(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("timer").unwrap();
+/// Post handler for a timed round. Is called when the user sets the timeout and therefore starts the timed round,
+/// drawing the categories that belong to that round; and when the "Success" or "Next" button are pressed.
+/// Redirects to the timer page.
+async fn post_start_timer(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Form(input): Form<RoundInput>) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let mut game = room.game.lock().unwrap();
+    if !is_current(&game, &input.token) {
+        return Ok(Redirect::to("/timer"));
+    }
+    room.check_turn(&game).map_err(|e| game_error(&state, lang, e))?;
+
+    let transition = match (input.timeout, input.success) {
+        // first request of the round: draw the categories and start the timer
+        (Some(timeout), _) => state.start(&mut game, timeout),
+        (None, Some(true)) => game.success(),
+        (None, Some(false)) => game.skip(),
+        (None, None) => Err(GameError::NotRolled),
+    };
+    transition.map_err(|e| game_error(&state, lang, e))?;
+    state.persist(&room.code, &mut game);
+
+    Ok(Redirect::to("/timer"))
+}
 
-    let round_state = state.round_state.lock().unwrap();
+/// Render the "timer" template for the current state of the `Game`,
+/// with the buttons only if this device may control the round.
+fn render_timer(state: &GameState, lang: Language, game: &Game, room: &Room) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let round = game.round();
 
-    let rendered = template
-        .render(context! {
-            title => "~ Play ~",
-            timeout => round_state.timeout,
-            letter => round_state.letter,
-            category => round_state.category,
-            current_index => round_state.current_index,
-            rest => round_state.reduced_card
+    state.render(lang, "timer", context! {
+            lang => lang,
+            title => lang.t("title_play"),
+            running => game.is_running(),
+            phase => game.phase().name(),
+            timeout => game.remaining(),
+            percent => game.remaining_fraction().map(|f| (f * 100.0).round() as u32),
+            level => game.time_level(),
+            letter => game.letter(),
+            category => round.and_then(|r| r.current_category()),
+            current_index => round.map(|r| r.current_index()),
+            rest => round.map(|r| r.reduced_card()),
+            token => game.round_token(),
+            player => game.current_player(),
+            your_turn => room.check_turn(game).is_ok(),
+            last_action => round.and_then(|r| r.events().last()).map(|e| &e.action),
+            sounds => state.settings.config.ui.sounds,
         })
-        .unwrap();
-
-    Ok(Html(rendered))
-}
-
-/// Post handler for a timed round. Is called when the user sets the timeout and therefore starts the timed round;
-/// drawing the categories that belong to that round; and handling the state when the "Success" or "Next" button are pressed.
-async fn post_start_timer(State(state): State<Arc<GameState>>, Form(input): Form<RoundInput>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("timer").unwrap();
-    let category: String;
-
-    let mut round_state = state.round_state.lock().unwrap();
-    let categories = state.categories.lock().unwrap();
-    let mut current_index = round_state.current_index.unwrap_or(0);
-    let mut category_amount;
-
-    let (reduced_card, complete_card) = match &round_state.complete_card {
-        // not first round, need to handle the state
-        Some(c) => {
-            let cc = c.to_vec();
-            let mut rc = round_state.reduced_card.clone().unwrap();
-            let success = input.success.unwrap();
-            category_amount = round_state.reduced_card.as_ref().unwrap().len();
-            if success {
-                // if successful: remove the category from the set
-                let old_category = round_state.category.clone().unwrap();
-                let i = rc.iter().position(|x| *x == old_category).unwrap();
-                rc.remove(i);
-                category_amount -= 1;
-                // since an element was removed, do nothing to the index or `-1` (if last element)
-                current_index = if i == 0 || i < category_amount { i } else { i - 1 };
-            } else {
-                // not successful (next button):
-                // since no element was removed, either go back to 0 (if last element) or increase index by 1
-                current_index = if current_index == (category_amount - 1) { 0 } else { current_index + 1 };
-            }
-            (rc, cc)
-        },
-        // first round, need to draw the new categories
-        None => {
-            category_amount = 6; // potential todo: make this configurable?
-            let dc = cards::draw_card(&categories, category_amount as u32);
-            (dc.clone(), dc)
+}
+
+/// Post handler to stop the round if it is still running. Redirects to its results,
+/// or to the round page if no round was played yet.
+async fn post_result(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let mut game = room.game.lock().unwrap();
+    if game.is_running() {
+        room.check_turn(&game).map_err(|e| game_error(&state, lang, e))?;
+        game.finish();
+        state.persist(&room.code, &mut game);
+    }
+
+    match game.round() {
+        Some(_) => Ok(Redirect::to("/result")),
+        None => Ok(Redirect::to("/round")),
+    }
+}
+
+/// Get handler to display a rounds results. Redirects to the round page if no round was
+/// played yet.
+async fn handler_result(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room) -> Result<Response, (StatusCode, Html<String>)> {
+    let game = room.game.lock().unwrap();
+    let Some(round) = game.round() else {
+        return Ok(Redirect::to("/round").into_response());
+    };
+
+    let rendered = state.render(lang, "result", context! {
+            lang => lang,
+            title => lang.t("title_result"),
+            timeout => game.timeout(),
+            letter => game.letter(),
+            card => round.complete_card(),
+            rest => round.reduced_card(),
+            timeline => Some(round.events()).filter(|_| state.settings.config.ui.show_timeline),
+            game_id => game.id(),
+            collections => cards::collections(),
+            game_collections => game.collections(),
+        })?;
+
+    Ok(rendered.into_response())
+}
+
+/// Get handler for a read-only view of the round, e.g. for a TV while a player holds the phone
+/// with the buttons. Shows letter, category and countdown in huge type and keeps polling the
+/// game, so it follows every Success/Next of the controller page.
+async fn handler_display(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let game = room.game.lock().unwrap();
+    let running = game.is_running();
+    let status = match game.phase() {
+        Phase::Setup => match game.current_player() {
+            Some(player) => lang.format("display_rolls", &[("player", player)]),
+            None => lang.t("display_waiting").to_string(),
         },
+        Phase::Rolled { .. } => lang.t("display_ready").to_string(),
+        Phase::Running { .. } => game.current_player().cloned().unwrap_or_default(),
+        Phase::Finished { .. } => lang.t("display_over").to_string(),
     };
-    // if all categories were successfully challenged (`reduced_card` is empty), simply put a placeholder
-    // since the view does not display a `category` then anyway
-    category = if reduced_card.len() == 0 { "".to_string() } else { reduced_card[current_index].clone() };
-    // first round only setup
-    if input.timeout.is_some() {
-        round_state.update_timeout(input.timeout);
-        round_state.update_complete_card(Some(complete_card.clone()));
-    }
-    round_state.update_reduced_card(Some(reduced_card.clone()));
-    round_state.update_category(Some(category));
-    round_state.update_current_index(Some(current_index));
-
-    let rendered = template
-        .render(context! {
-            title => "~ Play ~",
-            timeout => round_state.timeout,
-            letter => round_state.letter,
-            category => round_state.category,
-            current_index => round_state.current_index,
-            rest => round_state.reduced_card
-        })
-        .unwrap();
-
-    Ok(Html(rendered))
-}
-
-/// Get handler to display a rounds results.
-/// Better be save than sorry: delete the `RoundState` here too.
-async fn handler_result(State(state): State<Arc<GameState>>) -> Result<Html<String>, StatusCode> {
-    let template = state.environment.get_template("result").unwrap();
-
-    let mut round_state = state.round_state.lock().unwrap();
-    let old_timeout = round_state.timeout.clone();
-    let old_letter = round_state.letter.clone();
-    let old_card = round_state.complete_card.clone();
-    let old_rest = round_state.reduced_card.clone();
-    *round_state = RoundState::empty();
-
-    let rendered = template
-        .render(context! {
-            title => "Round Results",
-            timeout => old_timeout,
-            letter => old_letter,
-            card => old_card,
-            rest => old_rest,
-        })
-        .unwrap();
 
-    Ok(Html(rendered))
+    let rendered = state.render(lang, "display", context! {
+            lang => lang,
+            title => lang.t("title_display"),
+            status => status,
+            letter => game.letter(),
+            category => game.round().and_then(|r| r.current_category()).filter(|_| running),
+            remaining => game.remaining().filter(|_| running),
+            percent => game.remaining_fraction().filter(|_| running).map(|f| (f * 100.0).round() as u32),
+            level => game.time_level().filter(|_| running),
+            sounds => state.settings.config.ui.sounds,
+        })?;
+
+    Ok(rendered)
+}
+
+/// Post handler to open a new room with a new game, which this device joins.
+/// Redirects to the "Prepare Game" page.
+async fn post_rooms(State(state): State<Arc<GameState>>) -> impl IntoResponse {
+    let mut game = new_game(&state.settings);
+    let code = state.rooms.open(game.clone());
+    state.persist(&code, &mut game);
+
+    (join_cookies(&code, None), Redirect::to("/start"))
+}
+
+#[derive(Deserialize, Debug)]
+/// Room code typed in on the home page
+struct JoinCodeInput {
+    code: String,
+}
+
+/// Get handler for the room code typed in on the home page, redirects to the room.
+async fn handler_join_code(Query(input): Query<JoinCodeInput>) -> Redirect {
+    let code: String = input.code.trim().to_uppercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    Redirect::to(&format!("/join/{code}"))
+}
+
+/// Get handler to join room `code`. Asks for the name of the player using this device.
+async fn handler_join(State(state): State<Arc<GameState>>, Lang(lang): Lang, UrlPath(code): UrlPath<String>) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let game = state.rooms.get(&code).ok_or_else(|| unknown_room(&state, lang, &code))?;
+
+    let rendered = state.render(lang, "join", context! {
+            lang => lang,
+            title => lang.t("title_join"),
+            room => code.to_uppercase(),
+            players => game.lock().unwrap().players(),
+        })?;
+
+    Ok(rendered)
+}
+
+#[derive(Deserialize, Debug)]
+/// Name of the player joining from this device, empty for a device shared by everyone
+struct JoinInput {
+    name: String,
+}
+
+/// Post handler to join room `code`, adding the player to the game if they are new.
+/// Remembers room and player in cookies and redirects to the round.
+async fn post_join(State(state): State<Arc<GameState>>, Lang(lang): Lang, UrlPath(code): UrlPath<String>, Form(input): Form<JoinInput>) -> Result<impl IntoResponse, (StatusCode, Html<String>)> {
+    let code = code.to_uppercase();
+    let game = state.rooms.get(&code).ok_or_else(|| unknown_room(&state, lang, &code))?;
+    let name = input.name.trim();
+
+    let mut game = game.lock().unwrap();
+    if !name.is_empty() && !game.players().iter().any(|p| p == name) {
+        let mut players = game.players().clone();
+        players.push(name.to_string());
+        game.set_players(players).map_err(|e| game_error(&state, lang, e))?;
+        state.persist(&code, &mut game);
+    }
+
+    Ok((join_cookies(&code, Some(name)), Redirect::to("/round")))
+}
+
+/// Post handler to leave the joined room, this device plays in the default room afterwards.
+async fn post_leave() -> impl IntoResponse {
+    (join_cookies("", None), Redirect::to("/"))
+}
+
+/// Response for a room code that doesn't exist
+fn unknown_room(state: &GameState, lang: Language, code: &str) -> (StatusCode, Html<String>) {
+    message_page(state, lang, StatusCode::NOT_FOUND, lang.t("title_unknown_room"), lang.format("unknown_room", &[("code", &code.to_uppercase())]))
+}
+
+/// Get handler to display how often each category was drawn and solved over all games,
+/// highlighting the hardest and easiest category of each collection.
+async fn handler_statistics(State(state): State<Arc<GameState>>, Lang(lang): Lang) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let statistics = Statistics::load(&state.settings.data_dir);
+    let collections: Vec<_> = cards::collections().iter()
+        .map(|name| statistics.collection(name, &cards::load_categories(name, None)))
+        .collect();
+
+    let rendered = state.render(lang, "statistics", context! {
+            lang => lang,
+            title => lang.t("title_statistics"),
+            collections => collections,
+        })?;
+
+    Ok(rendered)
+}
+
+/// Get handler to list all stored games, the latest first.
+async fn handler_history(State(state): State<Arc<GameState>>, Lang(lang): Lang) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let rendered = state.render(lang, "history", context! {
+            lang => lang,
+            title => lang.t("title_history"),
+            games => GameRecord::load_all(&state.settings.data_dir),
+        })?;
+
+    Ok(rendered)
+}
+
+/// Get handler to display all rounds of one stored game.
+async fn handler_history_game(State(state): State<Arc<GameState>>, Lang(lang): Lang, UrlPath(id): UrlPath<String>) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let game = GameRecord::load(&state.settings.data_dir, &id)
+        .ok_or_else(|| error_page(&state, lang, StatusCode::NOT_FOUND, lang.format("error_unknown_game", &[("id", &id)])))?;
+
+    let rendered = state.render(lang, "history", context! {
+            lang => lang,
+            title => lang.t("title_history"),
+            game => game,
+        })?;
+
+    Ok(rendered)
+}
+
+#[derive(Deserialize, Debug)]
+/// Layout of a printed sheet, a printable page if not given
+struct PrintInput {
+    format: Option<Format>,
+}
+
+#[derive(Deserialize, Debug)]
+/// Category cards to print: the collection to draw from (the ones of the game if not given),
+/// how many cards and their layout
+struct PrintCardsInput {
+    collection: Option<String>,
+    count: Option<u32>,
+    format: Option<Format>,
+}
+
+/// Largest number of category cards printed at once
+const MAX_PRINTED_CARDS: u32 = 100;
+
+/// Get handler for a printable sheet with the letters, cards and results of a stored game.
+async fn handler_print_game(State(state): State<Arc<GameState>>, Lang(lang): Lang, UrlPath(id): UrlPath<String>, Query(input): Query<PrintInput>) -> Result<Response, (StatusCode, Html<String>)> {
+    let game = GameRecord::load(&state.settings.data_dir, &id)
+        .ok_or_else(|| error_page(&state, lang, StatusCode::NOT_FOUND, lang.format("error_unknown_game", &[("id", &id)])))?;
+
+    sheet_response(&state, lang, &game.sheet(), input.format.unwrap_or_default())
+}
+
+/// Get handler for random category cards to play on paper.
+async fn handler_print_cards(State(state): State<Arc<GameState>>, Lang(lang): Lang, room: Room, Query(input): Query<PrintCardsInput>) -> Result<Response, (StatusCode, Html<String>)> {
+    let (collections, language) = {
+        let game = room.game.lock().unwrap();
+        let collections = match input.collection.filter(|name| !name.is_empty()) {
+            Some(name) => vec![name],
+            None => game.collections().clone(),
+        };
+        (collections, game.language())
+    };
+    if collections.is_empty() {
+        return Err(game_error(&state, lang, GameError::NoCategories));
+    }
+    if let Some(unknown) = collections.iter().find(|name| !cards::is_collection(name)) {
+        return Err(game_error(&state, lang, GameError::UnknownCollection(unknown.clone())));
+    }
+    let count = input.count.unwrap_or(8).clamp(1, MAX_PRINTED_CARDS);
+    let sheet = Sheet::cards(&collections, language, count, state.settings.config.rules.card_size, &mut *state.rng.lock().unwrap());
+
+    sheet_response(&state, lang, &sheet, input.format.unwrap_or_default())
+}
+
+/// Response with `sheet` as printable page or plain text
+fn sheet_response(state: &GameState, lang: Language, sheet: &Sheet, format: Format) -> Result<Response, (StatusCode, Html<String>)> {
+    let rendered = render_sheet(&state.templates, lang, sheet, format)
+        .map_err(|_| error_page(state, lang, StatusCode::INTERNAL_SERVER_ERROR, lang.t("error_render").to_string()))?;
+    Ok(match format {
+        Format::Html => Html(rendered).into_response(),
+        Format::Text => ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], rendered).into_response(),
+    })
+}
+
+/// `sheet` in `format`, the printable page is rendered with the "print" template
+pub(crate) fn render_sheet(templates: &Templates, lang: Language, sheet: &Sheet, format: Format) -> Result<String, minijinja::Error> {
+    match format {
+        Format::Html => templates.render("print", context! {
+                lang => lang,
+                title => sheet.title(lang),
+                sheet => sheet,
+            }),
+        Format::Text => Ok(sheet.text(lang)),
+    }
 }
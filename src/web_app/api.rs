@@ -0,0 +1,267 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::cards;
+use crate::game::{Game, GameError, TimeLevel};
+use crate::history::RoundRecord;
+use crate::i18n::Language;
+
+use super::rooms::Room;
+use super::{error_status, new_game, GameState};
+
+/// Versioned JSON API, nested under `/api/v1`. Drives the same `Game` as the HTML pages,
+/// in the room chosen by the cookies of the device.
+pub fn router() -> Router<Arc<GameState>> {
+    Router::new()
+        .route("/collections", get(get_collections))
+        .route("/game", get(get_game).post(post_game))
+        .route("/game/collections", post(post_collection))
+        .route("/game/players", post(post_players))
+        .route("/game/language", post(post_language))
+        .route("/game/roll", post(post_roll))
+        .route("/game/round", post(post_round))
+        .route("/game/round/success", post(post_success))
+        .route("/game/round/next", post(post_next))
+        .route("/game/round/finish", post(post_finish))
+        .route("/game/result", get(get_result))
+}
+
+/// Error response of the API
+#[derive(Serialize, Debug)]
+pub struct ErrorResponse {
+    error: String,
+}
+
+type ApiResult<T> = Result<Json<T>, (StatusCode, Json<ErrorResponse>)>;
+
+fn api_error(error: GameError) -> (StatusCode, Json<ErrorResponse>) {
+    (error_status(&error), Json(ErrorResponse { error: error.to_string() }))
+}
+
+/// Current state of the game
+#[derive(Serialize, Debug)]
+pub struct GameResponse {
+    room: String,
+    id: String,
+    started: String,
+    collections: Vec<String>,
+    /// Language the categories are played in, `null` for as written
+    language: Option<Language>,
+    players: Vec<String>,
+    current_player: Option<String>,
+    category_count: usize,
+    /// One of `setup`, `rolled`, `running` and `finished`
+    phase: &'static str,
+    letter: Option<char>,
+    timeout: Option<u32>,
+    remaining: Option<u32>,
+    /// How urgent the remaining time is: `plenty`, `low` or `critical`
+    time_level: Option<TimeLevel>,
+    /// Category that is currently played
+    category: Option<String>,
+    /// Categories of the round that were not solved yet
+    open_categories: Option<Vec<String>>,
+}
+
+impl GameResponse {
+    pub fn new(room: &str, game: &Game) -> GameResponse {
+        let round = game.round();
+        GameResponse
+            { room: room.to_string()
+            , id: game.id().to_string()
+            , started: game.started().to_string()
+            , collections: game.collections().clone()
+            , language: game.language()
+            , players: game.players().clone()
+            , current_player: game.current_player().cloned()
+            , category_count: game.categories().len()
+            , phase: game.phase().name()
+            , letter: game.letter()
+            , timeout: game.timeout()
+            , remaining: game.remaining()
+            , time_level: game.time_level()
+            , category: round.and_then(|r| r.current_category()).cloned()
+            , open_categories: round.map(|r| r.reduced_card().clone())
+            }
+    }
+}
+
+/// Input to create a new game, all fields are optional
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct NewGameInput {
+    collections: Vec<String>,
+    players: Vec<String>,
+    language: Option<Language>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LanguageInput {
+    language: Option<Language>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CollectionInput {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PlayersInput {
+    players: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RoundInput {
+    timeout: u32,
+}
+
+/// Names of all category collections
+async fn get_collections() -> Json<Vec<String>> {
+    Json(cards::collections())
+}
+
+/// Apply `transition` to the game of `room`, persist it and respond with the new state
+fn transition(state: &GameState, room: &Room, transition: impl FnOnce(&mut Game) -> Result<(), GameError>) -> ApiResult<GameResponse> {
+    let mut game = room.game.lock().unwrap();
+    transition(&mut game).map_err(api_error)?;
+    state.persist(&room.code, &mut game);
+    Ok(Json(GameResponse::new(&room.code, &game)))
+}
+
+async fn get_game(room: Room) -> Json<GameResponse> {
+    Json(GameResponse::new(&room.code, &room.game.lock().unwrap()))
+}
+
+/// Replace the current game by a new one, not while a round runs
+async fn post_game(State(state): State<Arc<GameState>>, room: Room, input: Option<Json<NewGameInput>>) -> ApiResult<GameResponse> {
+    let Json(input) = input.unwrap_or_default();
+    let mut game = new_game(&state.settings);
+    if input.language.is_some() {
+        state.set_language(&mut game, input.language).map_err(api_error)?;
+    }
+    for name in &input.collections {
+        state.add_collection(&mut game, name).map_err(api_error)?;
+    }
+    game.set_players(input.players).map_err(api_error)?;
+    transition(&state, &room, |g| {
+        room.check_turn(g)?;
+        g.start_over(game)
+    })
+}
+
+async fn post_collection(State(state): State<Arc<GameState>>, room: Room, Json(input): Json<CollectionInput>) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| state.add_collection(game, &input.name))
+}
+
+async fn post_language(State(state): State<Arc<GameState>>, room: Room, Json(input): Json<LanguageInput>) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| state.set_language(game, input.language))
+}
+
+async fn post_players(State(state): State<Arc<GameState>>, room: Room, Json(input): Json<PlayersInput>) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| game.set_players(input.players))
+}
+
+async fn post_roll(State(state): State<Arc<GameState>>, room: Room) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| {
+        room.check_turn(game)?;
+        state.roll(game).map(|_| ())
+    })
+}
+
+async fn post_round(State(state): State<Arc<GameState>>, room: Room, Json(input): Json<RoundInput>) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| {
+        room.check_turn(game)?;
+        state.start(game, input.timeout)
+    })
+}
+
+async fn post_success(State(state): State<Arc<GameState>>, room: Room) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| {
+        room.check_turn(game)?;
+        game.success()
+    })
+}
+
+async fn post_next(State(state): State<Arc<GameState>>, room: Room) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| {
+        room.check_turn(game)?;
+        game.skip()
+    })
+}
+
+async fn post_finish(State(state): State<Arc<GameState>>, room: Room) -> ApiResult<GameResponse> {
+    transition(&state, &room, |game| {
+        if !game.is_running() {
+            return Err(GameError::NotRunning);
+        }
+        room.check_turn(game)?;
+        game.finish();
+        Ok(())
+    })
+}
+
+/// Result of the running or finished round
+async fn get_result(room: Room) -> ApiResult<RoundRecord> {
+    let game = room.game.lock().unwrap();
+    match (game.letter(), game.timeout(), game.round()) {
+        (Some(letter), Some(timeout), Some(round)) => {
+            Ok(Json(RoundRecord::new(game.round_player().cloned(), letter, timeout, round)))
+        },
+        _ => Err(api_error(GameError::NotRunning)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories() -> Vec<String> {
+        ["A", "B"].iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn reports_the_phases_of_the_game() {
+        let mut game = Game::new();
+        game.add_collection("default", categories()).unwrap();
+
+        let response = GameResponse::new("ABCD", &game);
+        assert_eq!((response.room.as_str(), response.phase, response.letter), ("ABCD", "setup", None));
+        assert_eq!(response.category_count, 2);
+
+        game.roll('K').unwrap();
+        let response = GameResponse::new("ABCD", &game);
+        assert_eq!((response.phase, response.letter, response.category), ("rolled", Some('K'), None));
+
+        game.start(60, categories()).unwrap();
+        let response = GameResponse::new("ABCD", &game);
+        assert_eq!((response.phase, response.remaining, response.time_level), ("running", Some(60), Some(TimeLevel::Plenty)));
+        assert_eq!(response.category.as_deref(), Some("A"));
+        assert_eq!(response.open_categories, Some(categories()));
+
+        game.success().unwrap();
+        game.finish();
+        let response = GameResponse::new("ABCD", &game);
+        assert_eq!((response.phase, response.letter, response.timeout), ("finished", Some('K'), Some(60)));
+        assert_eq!(response.open_categories, Some(vec!["B".to_string()]));
+    }
+
+    #[test]
+    fn maps_errors_to_status_codes() {
+        let status = |error| api_error(error).0;
+        assert_eq!(status(GameError::UnknownCollection("party".to_string())), StatusCode::NOT_FOUND);
+        assert_eq!(status(GameError::InvalidTimeout { min: 1, max: 999 }), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(status(GameError::NotYourTurn("Anna".to_string())), StatusCode::FORBIDDEN);
+        for error in [GameError::NoCategories, GameError::NotRolled, GameError::NotRunning, GameError::RoundRunning] {
+            assert_eq!(status(error), StatusCode::CONFLICT);
+        }
+        let (_, Json(response)) = api_error(GameError::NotRunning);
+        assert_eq!(response.error, GameError::NotRunning.to_string());
+    }
+}
@@ -0,0 +1,217 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue},
+    response::AppendHeaders,
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::Rng;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::game::{Game, GameError};
+
+use super::GameState;
+
+/// Letters of room codes, without the ones that are easily mixed up (I/1, O/0)
+const CODE_LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 4;
+
+/// Rooms that nobody used for this long are closed, except the default room
+pub const ABANDONED_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Game of a room and when a device last used it
+struct Entry {
+    game: Arc<Mutex<Game>>,
+    used: Instant,
+}
+
+impl Entry {
+    fn new(game: Game, used: Instant) -> Entry {
+        Entry { game: Arc::new(Mutex::new(game)), used }
+    }
+}
+
+/// Registry of all games played at the same time, each in a room with a short code
+/// that other devices use to join it.
+pub struct Rooms {
+    games: Mutex<HashMap<String, Entry>>,
+    /// Room of devices that didn't join one
+    default_code: String,
+}
+
+impl Rooms {
+    /// Registry of the given games with how long ago they were last used. Room `default`
+    /// stays the default room if it was restored, or the only restored room if there is
+    /// no `default` yet; else a new room with the game of `fallback` is opened.
+    pub fn new(games: Vec<(String, Game, Duration)>, default: Option<String>, fallback: impl FnOnce() -> Game) -> Rooms {
+        let now = Instant::now();
+        let default_code = match default {
+            Some(code) => games.iter().any(|(c, _, _)| *c == code).then_some(code),
+            None if games.len() == 1 => Some(games[0].0.clone()),
+            None => None,
+        };
+        let mut rooms = Rooms
+            { games: Mutex::new(games.into_iter()
+                .map(|(code, game, unused)| (code, Entry::new(game, now.checked_sub(unused).unwrap_or(now))))
+                .collect())
+            , default_code: String::new()
+            };
+        rooms.default_code = match default_code {
+            Some(code) => code,
+            None => rooms.open(fallback()),
+        };
+        rooms
+    }
+
+    pub fn default_code(&self) -> &str {
+        &self.default_code
+    }
+
+    /// Game of room `code`, `None` if there is no such room. Counts as use of the room.
+    pub fn get(&self, code: &str) -> Option<Arc<Mutex<Game>>> {
+        let mut games = self.games.lock().unwrap();
+        let entry = games.get_mut(&code.to_uppercase())?;
+        entry.used = Instant::now();
+        Some(Arc::clone(&entry.game))
+    }
+
+    /// All rooms with their games
+    pub fn all(&self) -> Vec<(String, Arc<Mutex<Game>>)> {
+        self.games.lock().unwrap().iter().map(|(code, entry)| (code.clone(), Arc::clone(&entry.game))).collect()
+    }
+
+    /// Open a new room for `game` and return its code
+    pub fn open(&self, game: Game) -> String {
+        let mut games = self.games.lock().unwrap();
+        let mut rng = rand::thread_rng();
+        let code = loop {
+            let code: String = (0..CODE_LENGTH)
+                .map(|_| CODE_LETTERS[rng.gen_range(0..CODE_LETTERS.len())] as char)
+                .collect();
+            if !games.contains_key(&code) {
+                break code;
+            }
+        };
+        games.insert(code.clone(), Entry::new(game, Instant::now()));
+        code
+    }
+
+    /// Close the rooms other than the default room that weren't used for `after`
+    /// and return their codes
+    pub fn close_unused(&self, after: Duration) -> Vec<String> {
+        let mut games = self.games.lock().unwrap();
+        let closed: Vec<String> = games.iter()
+            .filter(|(code, entry)| **code != self.default_code && entry.used.elapsed() >= after)
+            .map(|(code, _)| code.clone())
+            .collect();
+        for code in &closed {
+            games.remove(code);
+        }
+        closed
+    }
+}
+
+/// Room of the device sending the request, chosen by the cookies set when joining.
+/// Devices that didn't join a room play in the default room.
+pub struct Room {
+    pub code: String,
+    pub game: Arc<Mutex<Game>>,
+    /// Player that joined from this device, `None` for devices shared by everyone
+    pub player: Option<String>,
+}
+
+impl Room {
+    /// Check that this device may control the current round, see `Game::check_turn`
+    pub fn check_turn(&self, game: &Game) -> Result<(), GameError> {
+        game.check_turn(self.player.as_deref())
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<GameState>> for Room {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<GameState>) -> Result<Self, Self::Rejection> {
+        // the room is shown and saved under its code, which is upper case
+        let joined = cookie(&parts.headers, "room")
+            .map(|code| code.to_uppercase())
+            .and_then(|code| Some((state.rooms.get(&code)?, code)));
+        let room = match joined {
+            Some((game, code)) => Room { code, game, player: cookie(&parts.headers, "player") },
+            None => {
+                let code = state.rooms.default_code().to_string();
+                let game = state.rooms.get(&code).unwrap();
+                Room { code, game, player: None }
+            },
+        };
+        Ok(room)
+    }
+}
+
+/// Value of cookie `name` of the request
+fn cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers.get_all(header::COOKIE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| percent_decode_str(value).decode_utf8().ok())
+        .map(|value| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Cookies that make this device play in room `code` as `player`
+pub fn join_cookies(code: &str, player: Option<&str>) -> AppendHeaders<[(header::HeaderName, HeaderValue); 2]> {
+    let player = utf8_percent_encode(player.unwrap_or_default(), NON_ALPHANUMERIC).to_string();
+    AppendHeaders(
+        [ (header::SET_COOKIE, set_cookie("room", code))
+        , (header::SET_COOKIE, set_cookie("player", &player))
+        ])
+}
+
+fn set_cookie(name: &str, value: &str) -> HeaderValue {
+    // an empty value removes the cookie
+    let max_age = if value.is_empty() { "; Max-Age=0" } else { "" };
+    HeaderValue::from_str(&format!("{name}={value}; Path=/; SameSite=Lax{max_age}")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn restored(codes: &[&str]) -> Vec<(String, Game, Duration)> {
+        codes.iter().map(|code| (code.to_string(), Game::new(), HOUR)).collect()
+    }
+
+    #[test]
+    fn keeps_the_saved_default_room() {
+        let rooms = Rooms::new(restored(&["ABCD", "WXYZ"]), Some("ABCD".to_string()), Game::new);
+        assert_eq!(rooms.default_code(), "ABCD");
+        let rooms = Rooms::new(restored(&["WXYZ"]), None, Game::new);
+        assert_eq!(rooms.default_code(), "WXYZ");
+    }
+
+    #[test]
+    fn opens_a_new_default_room_if_it_is_unclear() {
+        let rooms = Rooms::new(restored(&["ABCD", "WXYZ"]), None, Game::new);
+        assert!(!["ABCD", "WXYZ"].contains(&rooms.default_code()));
+        assert_eq!(rooms.all().len(), 3);
+        let rooms = Rooms::new(restored(&["ABCD"]), Some("GONE".to_string()), Game::new);
+        assert_ne!(rooms.default_code(), "ABCD");
+    }
+
+    #[test]
+    fn closes_unused_rooms_but_the_default_room() {
+        let rooms = Rooms::new(restored(&["ABCD", "EFGH", "WXYZ"]), Some("ABCD".to_string()), Game::new);
+        assert!(rooms.get("efgh").is_some());
+        assert_eq!(rooms.close_unused(HOUR), vec!["WXYZ".to_string()]);
+        assert!(rooms.get("WXYZ").is_none());
+        assert!(rooms.get("ABCD").is_some());
+        assert!(rooms.get("EFGH").is_some());
+    }
+}
@@ -0,0 +1,203 @@
+use minijinja::value::{Kwargs, Value};
+use minijinja::{AutoEscape, Environment, Error, ErrorKind, HtmlEscape};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::Theme;
+use crate::i18n::Language;
+
+/// Templates that are compiled in, by name
+const BUILTIN: &[(&str, &str)] = &[
+    ("layout", include_str!("../templates/layout.jinja")),
+    ("home", include_str!("../templates/home.jinja")),
+    ("categories", include_str!("../templates/categories.jinja")),
+    ("start", include_str!("../templates/start.jinja")),
+    ("round", include_str!("../templates/round.jinja")),
+    ("timer", include_str!("../templates/timer.jinja")),
+    ("result", include_str!("../templates/result.jinja")),
+    ("statistics", include_str!("../templates/statistics.jinja")),
+    ("history", include_str!("../templates/history.jinja")),
+    ("display", include_str!("../templates/display.jinja")),
+    ("join", include_str!("../templates/join.jinja")),
+    ("sounds", include_str!("../templates/sounds.jinja")),
+    ("progress", include_str!("../templates/progress.jinja")),
+    ("print", include_str!("../templates/print.jinja")),
+];
+
+/// Templates of the pages. A file `<name>.jinja` in the templates directory replaces the
+/// built-in template `name`, other files there can be included by the replaced ones.
+pub struct Templates {
+    directory: Option<PathBuf>,
+    /// Theme of devices that didn't choose one, the global `default_theme` of the templates
+    theme: Theme,
+    /// Read the templates again for every page, so changes show up without a restart
+    reload: bool,
+    environment: Environment<'static>,
+    /// Only the built-in templates, used when one of the directory can't be rendered
+    builtin: Environment<'static>,
+}
+
+impl Templates {
+    pub fn new(directory: Option<PathBuf>, reload: bool, theme: Theme) -> Templates {
+        let templates = Templates
+            { environment: environment(directory.clone(), theme)
+            , builtin: environment(None, theme)
+            , directory
+            , theme
+            , reload
+            };
+        templates.check();
+        templates
+    }
+
+    /// Report the templates of the directory that replace built-in ones, and the ones that
+    /// can't be used because of errors
+    fn check(&self) {
+        let Some(directory) = &self.directory else {
+            return;
+        };
+        if !directory.is_dir() {
+            eprintln!("Couldn't find the templates directory {}, using the built-in templates", directory.display());
+            return;
+        }
+        for (name, _) in BUILTIN {
+            if !directory.join(format!("{name}.jinja")).exists() {
+                continue;
+            }
+            match self.environment.get_template(name) {
+                Ok(_) => eprintln!("using template {name} of {}", directory.display()),
+                Err(e) => eprintln!("Couldn't load template {name}: {e:#}"),
+            }
+        }
+    }
+
+    /// Render template `name` with `context`. A template of the directory that fails is
+    /// reported and the built-in one is rendered instead, so a typo can't stop the game.
+    /// Fails only if the built-in one fails too.
+    pub fn render(&self, name: &str, context: Value) -> Result<String, Error> {
+        let reloaded;
+        let environment = if self.reload {
+            reloaded = environment(self.directory.clone(), self.theme);
+            &reloaded
+        } else {
+            &self.environment
+        };
+        match environment.get_template(name).and_then(|template| template.render(context.clone())) {
+            Ok(rendered) => Ok(rendered),
+            Err(e) => {
+                eprintln!("Couldn't render template {name}: {e:#}");
+                self.builtin.get_template(name).and_then(|template| template.render(context))
+                    .inspect_err(|e| eprintln!("Couldn't render the built-in template {name}: {e:#}"))
+            }
+        }
+    }
+}
+
+/// Environment that loads the templates from `directory`, falling back to the built-in ones
+fn environment(directory: Option<PathBuf>, theme: Theme) -> Environment<'static> {
+    let mut env = Environment::new();
+    // the names have no `.html` suffix, but every template renders HTML with names and
+    // categories typed in by the players
+    env.set_auto_escape_callback(|_| AutoEscape::Html);
+    env.set_loader(move |name| load(directory.as_deref(), name));
+    env.add_function("t", translate);
+    env.add_global("default_theme", Value::from_serialize(theme));
+    env
+}
+
+fn load(directory: Option<&Path>, name: &str) -> Result<Option<String>, Error> {
+    // names come from `extends` and `include` of the templates, they must stay in the directory
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Ok(None);
+    }
+    if let Some(directory) = directory {
+        match fs::read_to_string(directory.join(format!("{name}.jinja"))) {
+            Ok(source) => return Ok(Some(source)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(Error::new(ErrorKind::InvalidOperation, format!("Couldn't read template {name}")).with_source(e)),
+        }
+    }
+    Ok(BUILTIN.iter().find(|(builtin, _)| *builtin == name).map(|(_, source)| source.to_string()))
+}
+
+/// Template function `t("key", name=value)`: the text for `key` in the language `lang`
+/// of the template context, with the `{name}` placeholders filled in. The texts of the
+/// catalogs may contain markup, the values are escaped unless they are safe already.
+fn translate(state: &minijinja::State, key: &str, args: Kwargs) -> Result<Value, Error> {
+    let lang: Language = state.lookup("lang")
+        .and_then(|lang| lang.as_str()?.parse().ok())
+        .unwrap_or_default();
+    let mut text = lang.t(key).to_string();
+    for name in args.args() {
+        let value: Value = args.get(name)?;
+        let value = if value.is_safe() { value.to_string() } else { HtmlEscape(&value.to_string()).to_string() };
+        text = text.replace(&format!("{{{name}}}"), &value);
+    }
+    Ok(Value::from_safe_string(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+
+    const HOSTILE: &str = "<script>alert(1)</script>";
+
+    fn templates() -> Templates {
+        Templates::new(None, false, Theme::default())
+    }
+
+    #[test]
+    fn escapes_a_hostile_player_name() {
+        let rendered = templates().render("round", context! {
+                lang => "en",
+                title => "Round",
+                player => HOSTILE,
+                players => [HOSTILE],
+            }).unwrap();
+        assert!(!rendered.contains(HOSTILE));
+        assert!(rendered.contains("&lt;script&gt;alert(1)&lt;"));
+    }
+
+    #[test]
+    fn escapes_values_of_translations_but_keeps_their_markup() {
+        let rendered = templates().render("home", context! {
+                lang => "en",
+                title => "Home",
+                room => "ABCD",
+                me => HOSTILE,
+            }).unwrap();
+        assert!(!rendered.contains(HOSTILE));
+        assert!(rendered.contains("Room code: <b>ABCD</b>"));
+        assert!(rendered.contains("you play as <b>&lt;script&gt;alert(1)&lt;"));
+    }
+
+    #[test]
+    fn escapes_categories_of_imported_collections() {
+        let rendered = templates().render("categories", context! {
+                lang => "en",
+                title => "Categories",
+                categories => [HOSTILE],
+                collections => [context! { name => "party", language => "de", count => 1 }],
+            }).unwrap();
+        assert!(!rendered.contains(HOSTILE));
+    }
+
+    #[test]
+    fn escapes_categories_in_the_page_data() {
+        let rendered = templates().render("timer", context! {
+                lang => "en",
+                title => "Play",
+                running => true,
+                category => HOSTILE,
+            }).unwrap();
+        assert!(!rendered.contains(HOSTILE));
+    }
+
+    #[test]
+    fn reports_a_failing_builtin_template_instead_of_panicking() {
+        // the result page needs the card of a round
+        assert!(templates().render("result", context! { lang => "en", title => "Result" }).is_err());
+    }
+}